@@ -0,0 +1,340 @@
+// A language server for flow files, speaking LSP over stdio. It reports parse errors and
+// undeclared names as you type, and offers go-to-definition, find-references, hover,
+// completion of declared names and formatting with the canonical printer.
+use std::collections::HashMap;
+use std::error::Error;
+use std::process::exit;
+use std::str::FromStr;
+
+use flower_parser::arrange::NodeKind;
+use flower_parser::diagnostic::{self, Position, Severity};
+use flower_parser::symbols::{self, completions, definition, describe, symbol_at, Symbol};
+use flower_parser::{Flow, RawFlow};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, Formatting, GotoDefinition, HoverRequest, References, Request as LspRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DocumentFormattingParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, Range, ReferenceParams, ServerCapabilities,
+    TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Uri,
+};
+
+struct Document {
+    text: String,
+    symbols: Vec<Symbol>,
+    // The last version of the text that parsed, so completions keep working while the
+    // flow is being edited.
+    flow: Option<RawFlow>,
+    parses: bool,
+}
+
+// Positions in flow text count characters, while LSP counts UTF-16 code units from 0.
+fn to_lsp(text: &str, position: Position) -> lsp_types::Position {
+    let line = position.line.saturating_sub(1);
+    let character: usize = text
+        .split('\n')
+        .nth(line)
+        .unwrap_or_default()
+        .chars()
+        .take(position.column)
+        .map(char::len_utf16)
+        .sum();
+    lsp_types::Position::new(line as u32, character as u32)
+}
+
+fn from_lsp(text: &str, position: lsp_types::Position) -> Position {
+    let mut units = 0;
+    let column = text
+        .split('\n')
+        .nth(position.line as usize)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= position.character as usize
+        })
+        .count();
+    Position {
+        line: position.line as usize + 1,
+        column,
+    }
+}
+
+fn range(text: &str, start: Position, end: Position) -> Range {
+    Range::new(to_lsp(text, start), to_lsp(text, end))
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut document = Document {
+            text: String::new(),
+            symbols: Vec::new(),
+            flow: None,
+            parses: false,
+        };
+        document.update(text);
+        document
+    }
+
+    fn update(&mut self, text: String) {
+        let parsed = Flow::from_str(&text);
+        self.parses = parsed.is_ok();
+        self.symbols = match parsed {
+            Ok(flow) => {
+                let symbols = symbols::symbols(&flow);
+                self.flow = Some(flow.into());
+                symbols
+            }
+            Err(_) => Vec::new(),
+        };
+        self.text = text;
+        // Every parse leaves its text in proc-macro2's source map, which would otherwise grow
+        // for as long as the editor is open. No spans outlive this point.
+        proc_macro2::extra::invalidate_current_thread_spans();
+    }
+
+    fn diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        let diagnostics = diagnostic::parse_flow(&self.text).diagnostics;
+        proc_macro2::extra::invalidate_current_thread_spans();
+        diagnostics
+            .into_iter()
+            .map(|d| {
+                let start = d
+                    .start
+                    .map_or_else(Default::default, |p| to_lsp(&self.text, p));
+                let end = d.end.map_or(start, |p| to_lsp(&self.text, p));
+                let severity = match d.severity {
+                    Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
+                    Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+                };
+                lsp_types::Diagnostic {
+                    range: Range::new(start, end),
+                    severity: Some(severity),
+                    source: Some("flower".to_string()),
+                    message: d.message,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    fn symbol_at(&self, position: lsp_types::Position) -> Option<&Symbol> {
+        symbol_at(&self.symbols, from_lsp(&self.text, position))
+    }
+
+    fn location(&self, uri: &Uri, symbol: &Symbol) -> Location {
+        Location::new(uri.clone(), range(&self.text, symbol.start, symbol.end))
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Uri, Document>,
+}
+
+impl Server {
+    fn document(&self, position: &TextDocumentPositionParams) -> Option<&Document> {
+        self.documents.get(&position.text_document.uri)
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let document = self.document(&position)?;
+        let symbol = document.symbol_at(position.position)?;
+        let declaration = definition(&document.symbols, symbol)?;
+        let uri = &position.text_document.uri;
+        Some(GotoDefinitionResponse::Scalar(
+            document.location(uri, declaration),
+        ))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let position = params.text_document_position;
+        let document = self.document(&position)?;
+        let symbol = document.symbol_at(position.position)?;
+        let include_declaration = params.context.include_declaration;
+        let uri = &position.text_document.uri;
+        Some(
+            symbols::references(&document.symbols, symbol)
+                .into_iter()
+                .filter(|s| include_declaration || !s.declaration)
+                .map(|s| document.location(uri, s))
+                .collect(),
+        )
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.document(&position)?;
+        let symbol = document.symbol_at(position.position)?;
+        let flow = document.flow.as_ref()?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: describe(flow, symbol.kind, &symbol.name),
+            }),
+            range: Some(range(&document.text, symbol.start, symbol.end)),
+        })
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.document(&position)?;
+        let flow = document.flow.as_ref()?;
+        let at = from_lsp(&document.text, position.position);
+        let items = completions(&document.text, at, flow)
+            .into_iter()
+            .map(|(kind, name)| {
+                let (kind, detail) = match kind {
+                    NodeKind::Resource => (CompletionItemKind::STRUCT, "resource"),
+                    NodeKind::State => (CompletionItemKind::CLASS, "state"),
+                    NodeKind::Intermediate => (CompletionItemKind::EVENT, "intermediate"),
+                };
+                CompletionItem {
+                    label: name,
+                    kind: Some(kind),
+                    detail: Some(detail.to_string()),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+
+    // Replaces the whole document with its canonical form, or leaves it alone while it
+    // does not parse.
+    fn formatting(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        if !document.parses {
+            return None;
+        }
+        let formatted = document.flow.as_ref()?.to_string();
+        if formatted == document.text {
+            return Some(Vec::new());
+        }
+        let last = document.text.rsplit('\n').next().unwrap_or_default();
+        let end = Position {
+            line: document.text.split('\n').count(),
+            column: last.chars().count(),
+        };
+        let whole = Range::new(Default::default(), to_lsp(&document.text, end));
+        Some(vec![TextEdit::new(whole, formatted)])
+    }
+
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => respond::<GotoDefinition>(request, |p| self.definition(p)),
+            References::METHOD => respond::<References>(request, |p| self.references(p)),
+            HoverRequest::METHOD => respond::<HoverRequest>(request, |p| self.hover(p)),
+            Completion::METHOD => respond::<Completion>(request, |p| self.completion(p)),
+            Formatting::METHOD => respond::<Formatting>(request, |p| self.formatting(p)),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request `{}`", method),
+            ),
+        }
+    }
+
+    // Returns the diagnostics to publish for the document the notification changed.
+    fn notification(&mut self, notification: Notification) -> Option<PublishDiagnosticsParams> {
+        let (uri, diagnostics) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                    .ok()?;
+                let document = Document::new(params.text_document.text);
+                let diagnostics = document.diagnostics();
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), document);
+                (uri, diagnostics)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidChangeTextDocumentParams>(
+                        DidChangeTextDocument::METHOD,
+                    )
+                    .ok()?;
+                let uri = params.text_document.uri;
+                let document = self.documents.get_mut(&uri)?;
+                // The server asks for full syncs, so the last change holds the whole text.
+                document.update(params.content_changes.into_iter().last()?.text);
+                (uri, document.diagnostics())
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                    .ok()?;
+                self.documents.remove(&params.text_document.uri);
+                (params.text_document.uri, Vec::new())
+            }
+            _ => return None,
+        };
+        Some(PublishDiagnosticsParams::new(uri, diagnostics, None))
+    }
+}
+
+fn respond<R: LspRequest>(request: Request, f: impl FnOnce(R::Params) -> R::Result) -> Response {
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(request.id, f(params)),
+        Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".to_string(), " ".to_string()]),
+            ..Default::default()
+        }),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+fn serve(connection: Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = server.request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(params) = server.notification(notification) {
+                    let method = PublishDiagnostics::METHOD.to_string();
+                    let notification = Notification::new(method, params);
+                    connection
+                        .sender
+                        .send(Message::Notification(notification))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let (connection, io_threads) = Connection::stdio();
+    let served = serve(connection);
+    if let Err(e) = served.and_then(|()| Ok(io_threads.join()?)) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,230 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::str::FromStr;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use flower_codegen::{expand, format, parse_file, Options};
+use flower_parser::coverage::{CoverageMap, CoverageReport};
+use flower_parser::diff::diff;
+use flower_parser::paths::{covering_paths, Coverage};
+use flower_parser::rename::{rename, rust_mentions};
+use flower_parser::schema::{self, Mode};
+use flower_parser::{Flow, RawFlow};
+
+#[derive(Parser)]
+#[command(name = "flower", version, about = "Tools for `.flow` files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Enumerate transition paths that cover the flow
+    Paths {
+        input: PathBuf,
+        /// State every path starts from; defaults to the first declared state
+        #[arg(long)]
+        initial: Option<String>,
+        /// Cover every pair of adjacent transitions instead of every transition
+        #[arg(long)]
+        pairs: bool,
+        #[arg(long, value_enum, default_value_t = Format::Rust)]
+        format: Format,
+    },
+    /// Map a runtime coverage report onto the flow
+    Coverage {
+        input: PathBuf,
+        /// JSON report written by `flower-runtime` with the `coverage` feature, read together
+        /// with the per-process reports beside it
+        report: PathBuf,
+        /// Module path of the `flow!` to show; defaults to every flow in the report
+        #[arg(long)]
+        flow: Option<String>,
+        #[arg(long, value_enum, default_value_t = CoverageFormat::Json)]
+        format: CoverageFormat,
+    },
+    /// Write the Rust code `flow!` expands to
+    Codegen {
+        input: PathBuf,
+        /// Output file; defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Document each state's references, transitions and overlays
+        #[arg(long)]
+        docs: bool,
+    },
+    /// Show what changed between two flows
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+    /// Rename a type everywhere it appears in the flow
+    Rename {
+        input: PathBuf,
+        from: String,
+        to: String,
+        /// Also list where the Rust sources under this directory mention the old name
+        #[arg(long = "crate", value_name = "DIR")]
+        crate_dir: Option<PathBuf>,
+        /// Rewrite the input instead of printing to stdout
+        #[arg(short, long)]
+        in_place: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Rust,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CoverageFormat {
+    Json,
+    Dot,
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiffFormat {
+    Text,
+    Json,
+}
+
+// `.json` files hold a versioned `RawFlow` document; anything else is parsed as flow text.
+fn load(path: &Path) -> Result<RawFlow, Box<dyn Error>> {
+    let s = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|e| e == "json") {
+        Ok(schema::from_str(&s, Mode::Lenient)?)
+    } else {
+        Ok(Flow::from_str(&s)?.into())
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Paths {
+            input,
+            initial,
+            pairs,
+            format,
+        } => {
+            let flow = load(&input)?;
+            let initial = match initial.or_else(|| flow.states.first().map(|s| s.0.clone())) {
+                Some(initial) => initial,
+                None => return Err("the flow declares no states".into()),
+            };
+            let coverage = if pairs {
+                Coverage::AdjacentPairs
+            } else {
+                Coverage::Transitions
+            };
+            let paths = covering_paths(&flow, &initial, coverage);
+            match format {
+                Format::Rust => print!("{}", paths.to_rust_tests()),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&paths)?),
+            }
+            for requirement in &paths.unreachable {
+                let steps: Vec<_> = requirement.iter().map(|s| s.to_string()).collect();
+                eprintln!(
+                    "warning: unreachable from `{}`: {}",
+                    initial,
+                    steps.join(", ")
+                );
+            }
+        }
+        Command::Coverage {
+            input,
+            report,
+            flow: name,
+            format,
+        } => {
+            let flow = load(&input)?;
+            let report = CoverageReport::read(&report)?;
+            if let Some(name) = &name {
+                if !report.flows.contains_key(name) {
+                    return Err(format!("the report has no flow `{}`", name).into());
+                }
+            }
+            let map = CoverageMap::new(&flow, &report.select(name.as_deref()));
+            match format {
+                CoverageFormat::Json => println!("{}", serde_json::to_string_pretty(&map)?),
+                CoverageFormat::Dot => print!("{}", map.to_dot(&flow)),
+                CoverageFormat::Html => print!("{}", map.to_html()),
+            }
+            for covered in map.untested() {
+                eprintln!("warning: untested: {}", covered.name);
+            }
+        }
+        Command::Codegen {
+            input,
+            output,
+            docs,
+        } => {
+            let flow = parse_file(&input, &fs::read_to_string(&input)?)?;
+            let options = Options {
+                docs,
+                ..Options::default()
+            };
+            let code = format(expand(&flow, &options))?;
+            match output {
+                Some(output) => fs::write(output, code)?,
+                None => print!("{}", code),
+            }
+        }
+        Command::Diff { old, new, format } => {
+            let diff = diff(&load(&old)?, &load(&new)?);
+            match format {
+                DiffFormat::Text => print!("{}", diff),
+                DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+            }
+        }
+        Command::Rename {
+            input,
+            from,
+            to,
+            crate_dir,
+            in_place,
+        } => {
+            let mut flow = parse_file(&input, &fs::read_to_string(&input)?)?;
+            if rename(&mut flow, &from, &to)? == 0 {
+                eprintln!("warning: `{}` does not appear in the flow", from);
+            }
+            let flow = RawFlow::from(flow);
+            let text = if input.extension().is_some_and(|e| e == "json") {
+                schema::to_string_pretty(&flow) + "\n"
+            } else {
+                flow.to_string()
+            };
+            if in_place {
+                fs::write(&input, text)?;
+            } else {
+                print!("{}", text);
+            }
+            if let Some(dir) = crate_dir {
+                for mention in rust_mentions(&dir, &from)? {
+                    eprintln!(
+                        "{}:{}:{}: mentions `{}`",
+                        mention.path.display(),
+                        mention.start.line,
+                        mention.start.column + 1,
+                        from
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
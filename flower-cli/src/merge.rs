@@ -0,0 +1,87 @@
+// A git merge driver for flow files. With
+//
+//     # .gitattributes
+//     *.flow merge=flower
+//
+//     # .git/config
+//     [merge "flower"]
+//         name = flower three-way merge
+//         driver = flower-merge %O %A %B
+//
+// git merges `.flow` files section by section and writes the result in canonical form.
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+use std::str::FromStr;
+
+use clap::Parser;
+use flower_parser::merge::merge;
+use flower_parser::schema::{self, Mode};
+use flower_parser::{Flow, RawFlow};
+
+#[derive(Parser)]
+#[command(
+    name = "flower-merge",
+    version,
+    about = "Three-way merge of flow files; exits with 1 on conflicts"
+)]
+struct Cli {
+    /// The common ancestor (`%O`)
+    base: PathBuf,
+    /// Our version (`%A`), overwritten with the result
+    ours: PathBuf,
+    /// Their version (`%B`)
+    theirs: PathBuf,
+}
+
+// git hands the driver temporary files without the original extension, so JSON documents
+// are told apart by their first character.
+fn is_json(s: &str) -> bool {
+    s.trim_start().starts_with('{')
+}
+
+fn load(s: &str) -> Result<RawFlow, Box<dyn Error>> {
+    if is_json(s) {
+        Ok(schema::from_str(s, Mode::Lenient)?)
+    } else {
+        Ok(Flow::from_str(s)?.into())
+    }
+}
+
+// Returns whether the merge had conflicts.
+fn run(cli: Cli) -> Result<bool, Box<dyn Error>> {
+    let ours = fs::read_to_string(&cli.ours)?;
+    let merged = merge(
+        &load(&fs::read_to_string(&cli.base)?)?,
+        &load(&ours)?,
+        &load(&fs::read_to_string(&cli.theirs)?)?,
+    );
+    for conflict in &merged.conflicts {
+        eprintln!("conflict: {}", conflict);
+    }
+    let output = if is_json(&ours) {
+        schema::to_string_pretty(&merged.flow) + "\n"
+    } else {
+        // Flow text can carry the conflicts as comments for whoever resolves them.
+        let comments: String = merged
+            .conflicts
+            .iter()
+            .map(|c| format!("// conflict: {}\n", c))
+            .collect();
+        comments + &merged.flow.to_string()
+    };
+    fs::write(&cli.ours, output)?;
+    Ok(!merged.conflicts.is_empty())
+}
+
+fn main() {
+    match run(Cli::parse()) {
+        Ok(false) => {}
+        Ok(true) => exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::process::{Command, Output};
+
+fn flower(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_flower"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn paths() {
+    let output = flower(&["paths", "tests/fixtures/game.flow"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        r#"#[test]
+fn path_1_title_to_title() {
+    // Title >- Start -> Game
+    // Game >--> GameOver
+    // GameOver >--> Title
+    todo!()
+}
+"#
+    );
+
+    let output = flower(&[
+        "paths",
+        "tests/fixtures/game.flow",
+        "--format",
+        "json",
+        "--pairs",
+    ]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["paths"][0]["steps"].as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn coverage() {
+    let output = flower(&[
+        "coverage",
+        "tests/fixtures/game.flow",
+        "tests/fixtures/coverage.json",
+        "--flow",
+        "game",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: untested: Pause\nwarning: untested: GameOver >--> Title\nwarning: untested: Game ^ Pause\n"
+    );
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["transitions"][0]["count"], 2);
+
+    let output = flower(&[
+        "coverage",
+        "tests/fixtures/game.flow",
+        "tests/fixtures/coverage.json",
+        "--flow",
+        "menu",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn codegen() {
+    let output = flower(&["codegen", "tests/fixtures/game.flow"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        include_str!("fixtures/game.rs")
+    );
+
+    let output = flower(&["codegen", "tests/fixtures/game.flow", "--docs"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains(
+        "/// `Game`\n/// - borrows `Save` mutably\n/// - transitions to `GameOver`\n/// - can be overlaid by `Pause`\n///\n"
+    ));
+}
+
+#[test]
+fn rename() {
+    let output = flower(&[
+        "rename",
+        "tests/fixtures/game.flow",
+        "Game",
+        "Play",
+        "--crate",
+        "tests/fixtures",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("state: [Title, Play, Pause, GameOver]\n"));
+    assert!(stdout.contains("reference: [Play -< mut Save, Pause -< Save]\n"));
+    assert!(stdout.contains("overlay: [Play ^ Pause]\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("tests/fixtures/game.rs:49:18: mentions `Game`\n"));
+
+    let output = flower(&["rename", "tests/fixtures/game.flow", "Menu", "Play"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: `Menu` does not appear in the flow\n"
+    );
+
+    let output = flower(&["rename", "tests/fixtures/game.flow", "Game", "Pause"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: `Pause` already appears in the flow\n"
+    );
+}
+
+#[test]
+fn diff() {
+    let output = flower(&[
+        "diff",
+        "tests/fixtures/game.flow",
+        "tests/fixtures/game-v2.flow",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "~ state GameOver -> Results\n\
+        + state Menu\n\
+        ~ reference Pause -< Save is now mutable\n\
+        + transition Title >--> Menu\n\
+        + overlay Game ^ Menu\n"
+    );
+
+    let output = flower(&[
+        "diff",
+        "tests/fixtures/game.flow",
+        "tests/fixtures/game-v2.flow",
+        "--format",
+        "json",
+    ]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["renamed_states"][0]["to"], "Results");
+    assert_eq!(json["states"]["added"][0], "Menu");
+}
+
+#[test]
+fn merge_driver() {
+    let merge = |ours: &str| {
+        let path = std::env::temp_dir().join(format!("flower-merge-{}.flow", std::process::id()));
+        std::fs::write(&path, ours).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_flower-merge"))
+            .args(["tests/fixtures/game.flow"])
+            .arg(&path)
+            .arg("tests/fixtures/game-v2.flow")
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .output()
+            .unwrap();
+        let merged = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (output, merged)
+    };
+    let base = include_str!("fixtures/game.flow");
+
+    let (output, merged) = merge(&base.replace("[Save]", "[Save, Audio]"));
+    assert!(output.status.success());
+    assert_eq!(
+        merged,
+        "resource: [Save, Audio]\n\
+        state: [Title, Game, Pause, Results, Menu]\n\
+        intermediate: [Start]\n\
+        reference: [Game -< mut Save, Pause -< mut Save]\n\
+        transition: [\n    \
+            Title >- Start -> Game,\n    \
+            Game >--> Results,\n    \
+            Results >--> Title after 3s,\n    \
+            Title >--> Menu,\n\
+        ]\n\
+        overlay: [Game ^ Pause, Game ^ Menu]\n"
+    );
+
+    let (output, merged) = merge(&base.replace("    Pause -< Save,\n", ""));
+    assert_eq!(output.status.code(), Some(1));
+    let conflict = "reference `Pause -< Save` is removed by ours \
+        and changed to `Pause -< mut Save` by theirs";
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("conflict: {}\n", conflict)
+    );
+    assert!(merged.starts_with(&format!("// conflict: {}\nresource: [Save]\n", conflict)));
+    assert!(merged.contains("reference: [Game -< mut Save]\n"));
+}
@@ -0,0 +1,63 @@
+pub const FLOW: ::flower_runtime::FlowMeta = ::flower_runtime::FlowMeta {
+    resources: &["Save"],
+    states: &["Title", "Game", "Pause", "GameOver"],
+    intermediates: &["Start"],
+    references: &[
+        ::flower_runtime::ReferenceMeta {
+            state: "Game",
+            mutable: true,
+            resource: "Save",
+        },
+        ::flower_runtime::ReferenceMeta {
+            state: "Pause",
+            mutable: false,
+            resource: "Save",
+        },
+    ],
+    transitions: &[
+        ::flower_runtime::TransitionMeta {
+            from: "Title",
+            intermediate: ::core::option::Option::Some("Start"),
+            to: "Game",
+            after_ms: ::core::option::Option::None,
+        },
+        ::flower_runtime::TransitionMeta {
+            from: "Game",
+            intermediate: ::core::option::Option::None,
+            to: "GameOver",
+            after_ms: ::core::option::Option::None,
+        },
+        ::flower_runtime::TransitionMeta {
+            from: "GameOver",
+            intermediate: ::core::option::Option::None,
+            to: "Title",
+            after_ms: ::core::option::Option::Some(3000u64),
+        },
+    ],
+    overlays: &[
+        ::flower_runtime::OverlayMeta {
+            back: "Game",
+            front: "Pause",
+        },
+    ],
+};
+pub fn flow() -> ::flower_runtime::Flow {
+    ::flower_runtime::Flow::new()
+        .module(::core::module_path!())
+        .resource::<Save>("Save")
+        .state::<Title>("Title")
+        .state::<Game>("Game")
+        .state::<Pause>("Pause")
+        .state::<GameOver>("GameOver")
+        .intermediate::<Start>("Start")
+        .reference::<Game, Save>(true)
+        .reference::<Pause, Save>(false)
+        .transition::<Title, Start, Game>()
+        .transition::<Game, GameOver, GameOver>()
+        .timed_transition::<
+            GameOver,
+            Title,
+            Title,
+        >(::core::time::Duration::from_millis(3000u64))
+        .overlay::<Game, Pause>()
+}
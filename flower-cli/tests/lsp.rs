@@ -0,0 +1,210 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///game.flow";
+
+// Drives `flower-lsp` over stdio, keeping the notifications that arrive between responses.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    id: u64,
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_flower-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+            id: 0,
+            notifications: Vec::new(),
+        };
+        let capabilities = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(capabilities["capabilities"]["textDocumentSync"], 1);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            match header.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                _ if header.trim_end().is_empty() => break,
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.id += 1;
+        let id = self.id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                assert!(message.get("error").is_none(), "{}", message);
+                return message["result"].clone();
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(method, position(line, character))
+    }
+
+    // The diagnostics published since the last call, waiting for them if none arrived yet.
+    fn diagnostics(&mut self) -> Value {
+        let published = |m: &Value| m["method"] == "textDocument/publishDiagnostics";
+        while !self.notifications.iter().any(published) {
+            let message = self.receive();
+            self.notifications.push(message);
+        }
+        let index = self.notifications.iter().position(published).unwrap();
+        self.notifications.remove(index)["params"]["diagnostics"].clone()
+    }
+
+    fn change(&mut self, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": text }],
+            }),
+        );
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+fn position(line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": line, "character": character },
+    })
+}
+
+fn line(location: &Value) -> (u64, u64) {
+    let start = &location["range"]["start"];
+    (
+        start["line"].as_u64().unwrap(),
+        start["character"].as_u64().unwrap(),
+    )
+}
+
+#[test]
+fn language_server() {
+    let game = include_str!("fixtures/game.flow");
+    let mut client = Client::start();
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": URI, "languageId": "flow", "version": 1, "text": game },
+        }),
+    );
+    assert_eq!(client.diagnostics(), json!([]));
+
+    // `GameOver` in `Game >--> GameOver` leads to its entry in `state:`.
+    let definition = client.at("textDocument/definition", 9, 15);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(line(&definition), (1, 28));
+    assert_eq!(definition["range"]["end"]["character"], 36);
+
+    let mut params = position(0, 12);
+    params["context"] = json!({ "includeDeclaration": true });
+    let references = client.request("textDocument/references", params.clone());
+    let lines: Vec<_> = references.as_array().unwrap().iter().map(line).collect();
+    assert_eq!(lines, [(0, 11), (4, 16), (5, 13)]);
+    params["context"] = json!({ "includeDeclaration": false });
+    let references = client.request("textDocument/references", params);
+    assert_eq!(references.as_array().unwrap().len(), 2);
+
+    let hover = client.at("textDocument/hover", 4, 5);
+    assert_eq!(
+        hover["contents"]["value"],
+        "state `Game`\n\
+        - borrows `Save` mutably\n\
+        - is entered from `Title` through `Start`\n\
+        - transitions to `GameOver`\n\
+        - can be overlaid by `Pause`"
+    );
+
+    let edited = game.replace("Pause -< Save", "Pause -< ");
+    client.change(&edited);
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(line(&diagnostics[0]), (5, 13));
+    let completion = client.at("textDocument/completion", 5, 13);
+    let labels: Vec<_> = completion
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, ["Save"]);
+    let completion = client.at("textDocument/completion", 8, 13);
+    assert_eq!(completion[0]["label"], "Start");
+    assert_eq!(completion[0]["detail"], "intermediate");
+
+    let options = json!({ "tabSize": 4, "insertSpaces": true });
+    let format = |client: &mut Client| {
+        client.request(
+            "textDocument/formatting",
+            json!({ "textDocument": { "uri": URI }, "options": options }),
+        )
+    };
+    assert_eq!(format(&mut client), Value::Null);
+    client.change(&game.replace("state: [", "state:[   "));
+    client.diagnostics();
+    let edits = format(&mut client);
+    let references = "[Game -< mut Save, Pause -< Save]";
+    let formatted = game.replace(
+        "[\n    Game -< mut Save,\n    Pause -< Save,\n]",
+        references,
+    );
+    assert_eq!(edits[0]["newText"], formatted);
+    assert_eq!(
+        edits[0]["range"]["start"],
+        json!({ "line": 0, "character": 0 })
+    );
+    assert_eq!(
+        edits[0]["range"]["end"],
+        json!({ "line": 13, "character": 0 })
+    );
+    client.change(&formatted);
+    client.diagnostics();
+    assert_eq!(format(&mut client), json!([]));
+
+    client.shutdown();
+}
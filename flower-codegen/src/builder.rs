@@ -0,0 +1,214 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use quote::quote;
+use syn::{Ident, Path as SynPath, Visibility};
+
+use super::expand::{expand, format, Options};
+use super::file::parse_file;
+
+#[derive(Debug)]
+pub enum Error {
+    Io { path: PathBuf, error: io::Error },
+    Parse(String),
+    InvalidOption { option: &'static str, value: String },
+    OutDirNotSet,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::InvalidOption { option, value } => {
+                write!(f, "invalid {}: `{}`", option, value)
+            }
+            Error::OutDirNotSet => write!(
+                f,
+                "`OUT_DIR` is not set; call `out_dir` outside of build.rs"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Generates the code of `flow!` from a `.flow` or `.json` file in `build.rs`:
+//
+//     flower_codegen::Builder::new("flows/game.flow").module("game").generate()?;
+//
+// and `include!(concat!(env!("OUT_DIR"), "/game.rs"));` in the crate.
+pub struct Builder {
+    input: PathBuf,
+    out_dir: Option<PathBuf>,
+    module: Option<String>,
+    visibility: String,
+    derives: Vec<String>,
+    docs: bool,
+}
+
+impl Builder {
+    pub fn new(input: impl AsRef<Path>) -> Self {
+        Builder {
+            input: input.as_ref().to_path_buf(),
+            out_dir: None,
+            module: None,
+            visibility: "pub".to_string(),
+            derives: ["Debug", "Clone", "Copy", "PartialEq", "Eq", "Hash"]
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            docs: false,
+        }
+    }
+
+    // Defaults to `OUT_DIR`.
+    pub fn out_dir(mut self, out_dir: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(out_dir.as_ref().to_path_buf());
+        self
+    }
+
+    // Wraps the generated items in `mod <module>` and names the output `<module>.rs`;
+    // without it the output is named after the input file.
+    pub fn module(mut self, module: &str) -> Self {
+        self.module = Some(module.to_string());
+        self
+    }
+
+    // Visibility of the generated items, e.g. `pub(crate)`; an empty string makes them private.
+    pub fn visibility(mut self, visibility: &str) -> Self {
+        self.visibility = visibility.to_string();
+        self
+    }
+
+    // Replaces the derives of the generated `StateId` enum.
+    pub fn derives<I, S>(mut self, derives: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.derives = derives.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn docs(mut self, docs: bool) -> Self {
+        self.docs = docs;
+        self
+    }
+
+    // Writes the generated source and returns its path.
+    pub fn generate(self) -> Result<PathBuf, Error> {
+        let invalid = |option, value: &str| Error::InvalidOption {
+            option,
+            value: value.to_string(),
+        };
+        let visibility: Visibility = syn::parse_str(&self.visibility)
+            .map_err(|_| invalid("visibility", &self.visibility))?;
+        let derives = self
+            .derives
+            .iter()
+            .map(|d| syn::parse_str::<SynPath>(d).map_err(|_| invalid("derive", d)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let module = self
+            .module
+            .as_deref()
+            .map(|m| syn::parse_str::<Ident>(m).map_err(|_| invalid("module", m)))
+            .transpose()?;
+
+        println!("cargo:rerun-if-changed={}", self.input.display());
+        let io = |path: &Path| {
+            let path = path.to_path_buf();
+            move |error| Error::Io { path, error }
+        };
+        let source = fs::read_to_string(&self.input).map_err(io(&self.input))?;
+        let flow = parse_file(&self.input, &source).map_err(Error::Parse)?;
+
+        let options = Options {
+            visibility: visibility.clone(),
+            state_id: Some(derives),
+            docs: self.docs,
+        };
+        let expanded = expand(&flow, &options);
+        let tokens = match &module {
+            Some(module) => quote! {
+                #visibility mod #module {
+                    use super::*;
+
+                    #expanded
+                }
+            },
+            None => expanded,
+        };
+        let code = format(tokens).map_err(|e| Error::Parse(e.to_string()))?;
+
+        let out_dir = match self.out_dir {
+            Some(out_dir) => out_dir,
+            None => PathBuf::from(env::var_os("OUT_DIR").ok_or(Error::OutDirNotSet)?),
+        };
+        let name = match &module {
+            Some(module) => module.to_string(),
+            None => self
+                .input
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "flow".to_string()),
+        };
+        let output = out_dir.join(name + ".rs");
+        fs::write(&output, code).map_err(io(&output))?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod builder_test {
+    use std::env;
+    use std::fs;
+
+    use super::{Builder, Error};
+
+    const FLOW_STR: &str = r#"resource: []
+state: [Title, Game]
+intermediate: []
+reference: []
+transition: [Title >--> Game]
+overlay: []
+"#;
+
+    #[test]
+    fn writes_formatted_module() {
+        let dir = env::temp_dir().join(format!("flower-codegen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("game.flow");
+        fs::write(&input, FLOW_STR).unwrap();
+
+        let output = Builder::new(&input)
+            .out_dir(&dir)
+            .module("game")
+            .visibility("pub(crate)")
+            .derives(["Debug"])
+            .generate()
+            .unwrap();
+        assert_eq!(output, dir.join("game.rs"));
+        let code = fs::read_to_string(&output).unwrap();
+        assert!(code.starts_with("pub(crate) mod game {\n    use super::*;\n"));
+        assert!(code.contains("    #[derive(Debug)]\n    pub(crate) enum StateId {\n        Title,\n        Game,\n    }\n"));
+        assert!(code.contains("    pub(crate) fn flow() -> ::flower_runtime::Flow {\n"));
+
+        let e = Builder::new(&input)
+            .out_dir(&dir)
+            .visibility("public")
+            .generate()
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            Error::InvalidOption {
+                option: "visibility",
+                ..
+            }
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+
+use flower_parser::Flow;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_quote, Ident, Path, Visibility};
+
+pub struct Options {
+    pub visibility: Visibility,
+    // Derives for a generated `StateId` enum with one variant per state; `None` omits the enum.
+    pub state_id: Option<Vec<Path>>,
+    // Documents each state's references, transitions and overlays on `flow()`.
+    pub docs: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            visibility: parse_quote!(pub),
+            state_id: None,
+            docs: false,
+        }
+    }
+}
+
+fn name<T: ToTokens>(t: &T) -> String {
+    t.to_token_stream().to_string()
+}
+
+pub fn expand(flow: &Flow, options: &Options) -> TokenStream {
+    let vis = &options.visibility;
+    let resources = flow.resources.punct.iter().map(|r| {
+        let name = name(r);
+        quote! { .resource::<#r>(#name) }
+    });
+    let states = flow.states.punct.iter().map(|s| {
+        let name = name(s);
+        quote! { .state::<#s>(#name) }
+    });
+    let intermediates = flow.intermediates.punct.iter().map(|i| {
+        let name = name(i);
+        quote! { .intermediate::<#i>(#name) }
+    });
+    let references = flow.references.punct.iter().map(|r| {
+        let state = &r.state;
+        let resource = &r.resource;
+        let mutable = r.mut_token.is_some();
+        quote! { .reference::<#state, #resource>(#mutable) }
+    });
+    let transitions = flow.transitions.punct.iter().map(|t| {
+        let from = &t.from;
+        let to = &t.to;
+        let via = match &t.intermediate {
+            Some(via) => quote! { #via },
+            None => quote! { #to },
+        };
+        match &t.delay {
+            Some(delay) => {
+                let millis = delay.millis;
+                quote! {
+                    .timed_transition::<#from, #via, #to>(
+                        ::core::time::Duration::from_millis(#millis)
+                    )
+                }
+            }
+            None => quote! { .transition::<#from, #via, #to>() },
+        }
+    });
+    let overlays = flow.overlays.punct.iter().map(|o| {
+        let back = &o.back;
+        let front = &o.front;
+        quote! { .overlay::<#back, #front>() }
+    });
+
+    let meta = meta(flow, vis);
+    let state_id = options
+        .state_id
+        .as_ref()
+        .map(|derives| state_id(flow, vis, derives));
+    let docs = if options.docs { docs(flow) } else { Vec::new() };
+
+    quote! {
+        #meta
+
+        #state_id
+
+        #(#[doc = #docs])*
+        #vis fn flow() -> ::flower_runtime::Flow {
+            ::flower_runtime::Flow::new()
+                .module(::core::module_path!())
+                #(#resources)*
+                #(#states)*
+                #(#intermediates)*
+                #(#references)*
+                #(#transitions)*
+                #(#overlays)*
+        }
+    }
+}
+
+fn meta(flow: &Flow, vis: &Visibility) -> TokenStream {
+    let resources = flow.resources.punct.iter().map(name);
+    let states = flow.states.punct.iter().map(name);
+    let intermediates = flow.intermediates.punct.iter().map(name);
+    let references = flow.references.punct.iter().map(|r| {
+        let state = name(&r.state);
+        let resource = name(&r.resource);
+        let mutable = r.mut_token.is_some();
+        quote! {
+            ::flower_runtime::ReferenceMeta {
+                state: #state,
+                mutable: #mutable,
+                resource: #resource,
+            }
+        }
+    });
+    let transitions = flow.transitions.punct.iter().map(|t| {
+        let from = name(&t.from);
+        let to = name(&t.to);
+        let intermediate = match &t.intermediate {
+            Some(via) => {
+                let via = name(via);
+                quote! { ::core::option::Option::Some(#via) }
+            }
+            None => quote! { ::core::option::Option::None },
+        };
+        let after_ms = match &t.delay {
+            Some(delay) => {
+                let millis = delay.millis;
+                quote! { ::core::option::Option::Some(#millis) }
+            }
+            None => quote! { ::core::option::Option::None },
+        };
+        quote! {
+            ::flower_runtime::TransitionMeta {
+                from: #from,
+                intermediate: #intermediate,
+                to: #to,
+                after_ms: #after_ms,
+            }
+        }
+    });
+    let overlays = flow.overlays.punct.iter().map(|o| {
+        let back = name(&o.back);
+        let front = name(&o.front);
+        quote! { ::flower_runtime::OverlayMeta { back: #back, front: #front } }
+    });
+
+    quote! {
+        #vis const FLOW: ::flower_runtime::FlowMeta = ::flower_runtime::FlowMeta {
+            resources: &[#(#resources),*],
+            states: &[#(#states),*],
+            intermediates: &[#(#intermediates),*],
+            references: &[#(#references),*],
+            transitions: &[#(#transitions),*],
+            overlays: &[#(#overlays),*],
+        };
+    }
+}
+
+fn docs(flow: &Flow) -> Vec<String> {
+    let mut docs = Vec::new();
+    for state in flow.states.punct.iter() {
+        let state = name(state);
+        if !docs.is_empty() {
+            docs.push(String::new());
+        }
+        docs.push(format!(" `{}`", state));
+        for r in flow
+            .references
+            .punct
+            .iter()
+            .filter(|r| name(&r.state) == state)
+        {
+            let mutably = if r.mut_token.is_some() {
+                " mutably"
+            } else {
+                ""
+            };
+            docs.push(format!(" - borrows `{}`{}", name(&r.resource), mutably));
+        }
+        for t in flow
+            .transitions
+            .punct
+            .iter()
+            .filter(|t| name(&t.from) == state)
+        {
+            let mut line = format!(" - transitions to `{}`", name(&t.to));
+            if let Some(via) = &t.intermediate {
+                line += &format!(" through `{}`", name(via));
+            }
+            if let Some(delay) = &t.delay {
+                line += &format!(" after {} ms", delay.millis);
+            }
+            docs.push(line);
+        }
+        for o in flow
+            .overlays
+            .punct
+            .iter()
+            .filter(|o| name(&o.back) == state)
+        {
+            docs.push(format!(" - can be overlaid by `{}`", name(&o.front)));
+        }
+    }
+    docs
+}
+
+pub fn format(tokens: TokenStream) -> syn::Result<String> {
+    Ok(prettyplease::unparse(&syn::parse2(tokens)?))
+}
+
+// The last path segment of a state type, or `State<index>` when that is not unique. The
+// fallback counts up past any name already taken, including a state's own.
+fn variants(flow: &Flow) -> Vec<Ident> {
+    let idents: Vec<Option<Ident>> = flow
+        .states
+        .punct
+        .iter()
+        .map(|s| s.0.path.segments.last().map(|s| s.ident.clone()))
+        .collect();
+    let unique = |ident: &Ident| idents.iter().filter(|i| i.as_ref() == Some(ident)).count() == 1;
+    let mut taken: HashSet<Ident> = idents
+        .iter()
+        .flatten()
+        .filter(|i| unique(i))
+        .cloned()
+        .collect();
+    idents
+        .iter()
+        .enumerate()
+        .map(|(index, ident)| match ident {
+            Some(ident) if unique(ident) => ident.clone(),
+            _ => {
+                let fallback = (index..)
+                    .map(|n| format_ident!("State{}", n))
+                    .find(|fallback| !taken.contains(fallback))
+                    .unwrap();
+                taken.insert(fallback.clone());
+                fallback
+            }
+        })
+        .collect()
+}
+
+fn state_id(flow: &Flow, vis: &Visibility, derives: &[Path]) -> TokenStream {
+    let variants = variants(flow);
+    let types = flow.states.punct.iter();
+    let names = flow.states.punct.iter().map(name);
+    let derive = (!derives.is_empty()).then(|| quote! { #[derive(#(#derives),*)] });
+
+    quote! {
+        #derive
+        #vis enum StateId {
+            #(#variants,)*
+        }
+
+        impl StateId {
+            #vis const ALL: &'static [StateId] = &[#(StateId::#variants),*];
+
+            #vis fn name(self) -> &'static str {
+                match self {
+                    #(StateId::#variants => #names,)*
+                }
+            }
+
+            #vis fn of(type_id: ::core::any::TypeId) -> ::core::option::Option<StateId> {
+                #(
+                    if type_id == ::core::any::TypeId::of::<#types>() {
+                        return ::core::option::Option::Some(StateId::#variants);
+                    }
+                )*
+                ::core::option::Option::None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod expand_test {
+    use std::str::FromStr;
+
+    use flower_parser::Flow;
+
+    use super::variants;
+
+    #[test]
+    fn fallback_variants_skip_state_names() {
+        let flow = Flow::from_str(
+            "resource: [] state: [State1, a::X, b::X] intermediate: [] reference: [] \
+            transition: [] overlay: []",
+        )
+        .unwrap();
+        let variants: Vec<_> = variants(&flow).iter().map(|v| v.to_string()).collect();
+        assert_eq!(variants, ["State1", "State2", "State3"]);
+    }
+}
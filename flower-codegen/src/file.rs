@@ -0,0 +1,71 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use flower_parser::diagnostic::error_position;
+use flower_parser::schema::{self, Mode};
+use flower_parser::{Flow, ParseError};
+use proc_macro2::Span;
+
+// Where parsing failed, from the error's span. Inside a macro every span parsed from a string
+// is the call site, which says nothing about the file, so the position is worked out from the
+// source instead.
+fn locate(e: &ParseError, source: &str) -> Option<(usize, usize)> {
+    let span = match e {
+        ParseError::LexError(e) => e.span(),
+        ParseError::SynError(e) => e.span(),
+    };
+    let start = span.start();
+    if start.line > 0 && start != Span::call_site().start() {
+        return Some((start.line, start.column + 1));
+    }
+    error_position(source).map(|p| (p.line, p.column + 1))
+}
+
+// Parses flow text, or a versioned `RawFlow` document when `path` ends in `.json`; unknown
+// fields in the document are errors.
+// Errors are prefixed with `path:line:column` where the position is known.
+pub fn parse_file(path: &Path, source: &str) -> Result<Flow, String> {
+    let display = path.display();
+    if path.extension().is_some_and(|e| e == "json") {
+        let raw = schema::from_str(source, Mode::Strict).map_err(|e| match e {
+            schema::Error::Json(e) if e.line() > 0 => {
+                let at = format!(" at line {} column {}", e.line(), e.column());
+                let message = e.to_string();
+                let message = message.strip_suffix(&at).unwrap_or(&message);
+                format!("{}:{}:{}: {}", display, e.line(), e.column(), message)
+            }
+            e => format!("{}: {}", display, e),
+        })?;
+        Flow::try_from(raw).map_err(|e| format!("{}: {}", display, e))
+    } else {
+        Flow::from_str(source).map_err(|e| match locate(&e, source) {
+            Some((line, column)) => format!("{}:{}:{}: {}", display, line, column, e),
+            None => format!("{}: {}", display, e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use std::path::Path;
+
+    use super::parse_file;
+
+    #[test]
+    fn errors_point_into_the_file() {
+        let source = "resource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: [Title >-> Title]\noverlay: []\n";
+        let e = parse_file(Path::new("game.flow"), source).unwrap_err();
+        assert!(e.starts_with("game.flow:5:"), "{}", e);
+
+        let e = parse_file(Path::new("game.json"), "{\n  \"states\": [,]\n}").unwrap_err();
+        assert!(e.starts_with("game.json:2:"), "{}", e);
+    }
+
+    #[test]
+    fn json_documents_are_strict() {
+        let source = r#"{"version": 1, "resources": [], "states": [], "intermediates": [],
+            "references": [], "transitions": [], "overlays": [], "comment": ""}"#;
+        let e = parse_file(Path::new("game.json"), source).unwrap_err();
+        assert_eq!(e, "game.json: unknown fields: comment");
+    }
+}
@@ -0,0 +1,7 @@
+mod builder;
+mod expand;
+mod file;
+
+pub use builder::{Builder, Error};
+pub use expand::{expand, format, Options};
+pub use file::parse_file;
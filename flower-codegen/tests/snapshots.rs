@@ -0,0 +1,67 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use flower_codegen::{expand, format, Options};
+use flower_parser::{schema, Flow, RawFlow};
+use similar::TextDiff;
+
+// Compares every `tests/flows/<name>.flow` against `tests/snapshots/<name>.{json,flow,rs}`.
+// Run with `UPDATE_SNAPSHOTS=1` to rewrite the snapshots instead.
+#[test]
+fn snapshots() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut inputs: Vec<PathBuf> = fs::read_dir(root.join("flows"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "flow"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty());
+
+    let mut failures = Vec::new();
+    for input in &inputs {
+        let name = input.file_stem().unwrap().to_string_lossy();
+        let parse = || {
+            Flow::from_str(&fs::read_to_string(input).unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", input.display(), e))
+        };
+        let raw = RawFlow::from(parse());
+        let pretty = raw.to_string();
+        let reparsed = RawFlow::from(Flow::from_str(&pretty).unwrap());
+        if reparsed != raw {
+            failures.push(format!("{}: pretty-printed flow parses differently", name));
+        }
+        let outputs = [
+            ("json", schema::to_string_pretty(&raw) + "\n"),
+            ("flow", pretty),
+            ("rs", format(expand(&parse(), &Options::default())).unwrap()),
+        ];
+        for (extension, actual) in outputs {
+            let snapshot = root
+                .join("snapshots")
+                .join(format!("{}.{}", name, extension));
+            if update {
+                fs::write(&snapshot, &actual).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&snapshot).unwrap_or_default();
+            if expected != actual {
+                let diff = TextDiff::from_lines(&expected, &actual);
+                failures.push(format!(
+                    "{}\n{}",
+                    snapshot.display(),
+                    diff.unified_diff().header("expected", "actual")
+                ));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) differ; rerun with UPDATE_SNAPSHOTS=1 to accept\n\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,11 @@
+pub const FLOW: ::flower_runtime::FlowMeta = ::flower_runtime::FlowMeta {
+    resources: &[],
+    states: &[],
+    intermediates: &[],
+    references: &[],
+    transitions: &[],
+    overlays: &[],
+};
+pub fn flow() -> ::flower_runtime::Flow {
+    ::flower_runtime::Flow::new().module(::core::module_path!())
+}
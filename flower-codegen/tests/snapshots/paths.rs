@@ -0,0 +1,38 @@
+pub const FLOW: ::flower_runtime::FlowMeta = ::flower_runtime::FlowMeta {
+    resources: &["assets :: Cache"],
+    states: &["menu :: Main", "menu :: Options", "game :: Level < 1 >"],
+    intermediates: &["loading :: Level < 1 >"],
+    references: &[
+        ::flower_runtime::ReferenceMeta {
+            state: "game :: Level < 1 >",
+            mutable: true,
+            resource: "assets :: Cache",
+        },
+    ],
+    transitions: &[
+        ::flower_runtime::TransitionMeta {
+            from: "menu :: Main",
+            intermediate: ::core::option::Option::Some("loading :: Level < 1 >"),
+            to: "game :: Level < 1 >",
+            after_ms: ::core::option::Option::None,
+        },
+    ],
+    overlays: &[
+        ::flower_runtime::OverlayMeta {
+            back: "menu :: Main",
+            front: "menu :: Options",
+        },
+    ],
+};
+pub fn flow() -> ::flower_runtime::Flow {
+    ::flower_runtime::Flow::new()
+        .module(::core::module_path!())
+        .resource::<assets::Cache>("assets :: Cache")
+        .state::<menu::Main>("menu :: Main")
+        .state::<menu::Options>("menu :: Options")
+        .state::<game::Level<1>>("game :: Level < 1 >")
+        .intermediate::<loading::Level<1>>("loading :: Level < 1 >")
+        .reference::<game::Level<1>, assets::Cache>(true)
+        .transition::<menu::Main, loading::Level<1>, game::Level<1>>()
+        .overlay::<menu::Main, menu::Options>()
+}
@@ -0,0 +1,92 @@
+// Generated from flower-parser's raw_ast, schema, diagnostic, arrange and edit types; do not edit.
+// Run `UPDATE_SNAPSHOTS=1 cargo test -p flower-parser typescript` to regenerate.
+
+/**
+ * A resource type, written as a Rust type path.
+ */
+export type Resource = string;
+
+/**
+ * A state type, written as a Rust type path.
+ */
+export type State = string;
+
+/**
+ * An intermediate type that a transition passes through, written as a Rust type path.
+ */
+export type Intermediate = string;
+
+/**
+ * A state borrowing a resource while it is on the stack.
+ */
+export type Reference = { state: State, 
+/**
+ * Whether the state borrows the resource mutably.
+ */
+mutable: boolean, resource: Resource, };
+
+/**
+ * A transition replacing `from` with `to`, optionally through an intermediate.
+ */
+export type Transition = { from: State, intermediate: Intermediate | null, to: State, 
+/**
+ * Makes the transition timed: it fires on its own after this many milliseconds.
+ */
+after_ms?: number, };
+
+/**
+ * A state `front` that can be pushed on top of `back`.
+ */
+export type Overlay = { back: State, front: State, };
+
+/**
+ * How the editor draws an element.
+ */
+export type LayoutNode = { x: number, y: number, colour?: string, collapsed: boolean, };
+
+/**
+ * A serialized flow: the format version followed by the flow itself.
+ */
+export type Flow = { 
+/**
+ * The format version; files without one are migrated from version 0.
+ */
+version: number, resources: Array<Resource>, states: Array<State>, intermediates: Array<Intermediate>, references: Array<Reference>, transitions: Array<Transition>, overlays: Array<Overlay>, 
+/**
+ * Where the editor draws each resource, state and intermediate, keyed by name.
+ */
+layout?: { [key in string]?: LayoutNode }, };
+
+export type Severity = "error" | "warning";
+
+/**
+ * `line` is 1-based and `column` is a 0-based count of characters, as in proc-macro2.
+ */
+export type Position = { line: number, column: number, };
+
+export type Diagnostic = { severity: Severity, message: string, start: Position | null, end: Position | null, };
+
+export type Outcome<T> = { ok: boolean, value: T | null, diagnostics: Array<Diagnostic>, };
+
+export type NodeKind = "resource" | "state" | "intermediate";
+
+export type Point = { x: number, y: number, };
+
+export type ArrangedNode = { name: string, kind: NodeKind, x: number, y: number, };
+
+export type EdgeKind = "transition" | "reference" | "overlay";
+
+export type ArrangedEdge = { kind: EdgeKind, from: string, to: string, points: Array<Point>, };
+
+export type Arrangement = { nodes: Array<ArrangedNode>, edges: Array<ArrangedEdge>, };
+
+/**
+ * An editing operation. Applying one returns its inverse, which undoes it exactly. Entries
+ * are added at `index`, or at the end when it is not given.
+ */
+export type Edit = { "op": "add_element", kind: NodeKind, name: string, index?: number, } | { "op": "remove_element", kind: NodeKind, name: string, } | { "op": "rename_element", kind: NodeKind, from: string, to: string, } | { "op": "add_reference", reference: Reference, index?: number, } | { "op": "remove_reference", reference: Reference, } | { "op": "toggle_mutable", state: string, resource: string, } | { "op": "add_transition", transition: Transition, index?: number, } | { "op": "remove_transition", transition: Transition, } | { "op": "add_overlay", overlay: Overlay, index?: number, } | { "op": "remove_overlay", overlay: Overlay, } | { "op": "set_layout", name: string, node: LayoutNode | null, } | { "op": "restore_entries", entries: Entries, } | { "op": "take_entries", entries: Entries, } | { "op": "batch", edits: Array<Edit>, };
+
+/**
+ * Entries paired with their indices in their section, in increasing order of index.
+ */
+export type Entries = { references: Array<[number, Reference]>, transitions: Array<[number, Transition]>, overlays: Array<[number, Overlay]>, };
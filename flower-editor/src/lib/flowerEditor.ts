@@ -7,8 +7,8 @@ import {
   KeyboardEventHandler,
   KeyboardEvent,
 } from "react";
-import { Flow } from "../@types/api";
-import { json2flow, flow2json } from "../pkg";
+import { Arrangement, Diagnostic, Flow, Outcome } from "../@types/api";
+import { Editor, arrangeFlow, parseFlow, printFlow } from "../pkg";
 
 export type FlowerEditor = {
   saveFlow: () => Promise<void>;
@@ -19,6 +19,14 @@ export type FlowerEditor = {
   ref: RefObject<HTMLCanvasElement>;
 };
 
+const logDiagnostics = (path: string, diagnostics: Diagnostic[]) => {
+  for (const { severity, message, start } of diagnostics) {
+    const at = start ? `${path}:${start.line}:${start.column + 1}` : path;
+    const log = severity === "error" ? console.error : console.warn;
+    log(`${at}: ${message}`);
+  }
+};
+
 export interface Size {
   width: number | undefined;
   height: number | undefined;
@@ -60,12 +68,39 @@ const fillObject = <O extends {}>(
   }
 };
 
+// Draws the nodes and edges computed by `arrangeFlow`, shifted by `x` and `y`.
+const drawArrangement = (
+  ctx: CanvasRenderingContext2D,
+  { nodes, edges }: Arrangement,
+  x: number,
+  y: number
+) => {
+  ctx.save();
+  ctx.translate(x, y);
+  for (const { kind, points } of edges) {
+    ctx.setLineDash(kind === "transition" ? [] : [4, 4]);
+    ctx.beginPath();
+    points.forEach((p, i) =>
+      i === 0 ? ctx.moveTo(p.x, p.y) : ctx.lineTo(p.x, p.y)
+    );
+    ctx.stroke();
+  }
+  ctx.textAlign = "center";
+  ctx.textBaseline = "middle";
+  for (const { name, x, y } of nodes) {
+    ctx.fillText(name, x, y);
+  }
+  ctx.restore();
+};
+
 export function useFlowerEditor(size: Size): FlowerEditor {
   const [flowPath, setFlowPath] = useState<string | null>(null);
 
   const canvasRef = useRef<HTMLCanvasElement>(null);
   const contextRef = useRef<CanvasRenderingContext2D | null>(null);
   const flowRef = useRef<Flow>();
+  const arrangementRef = useRef<Arrangement>();
+  const editorRef = useRef<Editor>();
   const rawFlowStrRef = useRef<string>();
   const frameRequestHandleRef = useRef<number>();
 
@@ -77,8 +112,13 @@ export function useFlowerEditor(size: Size): FlowerEditor {
 
       const { width, height } = ctx.canvas;
       ctx.clearRect(0, 0, width, height);
-      fillObject(ctx, flow, 0, 10);
-      rawFlowStrRef.current && fillObject(ctx, rawFlowStrRef.current, 200, 10);
+      if (arrangementRef.current) {
+        drawArrangement(ctx, arrangementRef.current, 40, 40);
+      } else {
+        fillObject(ctx, flow, 0, 10);
+        rawFlowStrRef.current &&
+          fillObject(ctx, rawFlowStrRef.current, 200, 10);
+      }
     }
 
     frameRequestHandleRef.current = requestAnimationFrame(draw);
@@ -94,15 +134,20 @@ export function useFlowerEditor(size: Size): FlowerEditor {
     };
   }, []);
 
+  const showFlow = (path: string, flow: Flow) => {
+    flowRef.current = flow;
+    const arranged: Outcome<Arrangement> = arrangeFlow(flow);
+    logDiagnostics(path, arranged.diagnostics);
+    arrangementRef.current = arranged.value ?? undefined;
+  };
+
   const saveFlow = async () => {
     const flowPath = await window.electron.showSaveFlowDialog();
     if (!!flowPath && flowRef.current) {
-      try {
-        const flowStr = json2flow(JSON.stringify(flowRef.current));
-        console.log({ flowStr });
-        await window.electron.writeFile(flowPath, flowStr);
-      } catch (e) {
-        console.warn({ e });
+      const outcome: Outcome<string> = printFlow(flowRef.current);
+      logDiagnostics(flowPath, outcome.diagnostics);
+      if (outcome.ok && outcome.value !== null) {
+        await window.electron.writeFile(flowPath, outcome.value);
       }
     }
   };
@@ -113,15 +158,17 @@ export function useFlowerEditor(size: Size): FlowerEditor {
       setFlowPath(loadFlowPath);
       const flowStr = await window.electron.readFile(loadFlowPath);
       console.log({ flowStr });
-      try {
-        const flow = JSON.parse(flow2json(flowStr));
-        console.log({ flow });
-        flowRef.current = flow;
+      const outcome: Outcome<Flow> = parseFlow(flowStr);
+      logDiagnostics(loadFlowPath, outcome.diagnostics);
+      if (outcome.ok && outcome.value !== null) {
+        const flow = outcome.value;
+        const loaded: Outcome<Editor> = Editor.load(flow);
+        logDiagnostics(loadFlowPath, loaded.diagnostics);
+        editorRef.current = loaded.value ?? undefined;
         rawFlowStrRef.current = flowStr;
+        showFlow(loadFlowPath, flow);
 
         draw(flow);
-      } catch (e) {
-        console.warn(e);
       }
     }
   };
@@ -132,7 +179,18 @@ export function useFlowerEditor(size: Size): FlowerEditor {
       setFlowPath(createFlowPath);
     }
   };
-  const handleKeyInput = (e: KeyboardEvent<HTMLCanvasElement>) => {};
+  // Ctrl+Z undoes the last edit and Ctrl+Shift+Z redoes it.
+  const handleKeyInput = (e: KeyboardEvent<HTMLCanvasElement>) => {
+    const editor = editorRef.current;
+    if (!editor || !(e.ctrlKey || e.metaKey) || e.key.toLowerCase() !== "z") {
+      return;
+    }
+    const outcome: Outcome<Flow> = e.shiftKey ? editor.redo() : editor.undo();
+    logDiagnostics(flowPath ?? "", outcome.diagnostics);
+    if (outcome.ok && outcome.value !== null) {
+      showFlow(flowPath ?? "", outcome.value);
+    }
+  };
 
   useEffect(() => {
     const canvas = canvasRef.current;
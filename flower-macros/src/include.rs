@@ -0,0 +1,29 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use flower_codegen::parse_file;
+use flower_parser::Flow;
+use proc_macro2::TokenStream;
+use syn::{Error, LitStr, Result};
+
+// Resolves `path` against `CARGO_MANIFEST_DIR`, the same way `include_str!` would resolve it
+// against the invoking file.
+fn resolve(path: &LitStr) -> Result<PathBuf> {
+    let dir = env::var_os("CARGO_MANIFEST_DIR")
+        .ok_or_else(|| Error::new(path.span(), "`CARGO_MANIFEST_DIR` is not set"))?;
+    Ok(Path::new(&dir).join(path.value()))
+}
+
+pub fn load(path: &LitStr) -> Result<(Flow, TokenStream)> {
+    let resolved = resolve(path)?;
+    let source = fs::read_to_string(&resolved)
+        .map_err(|e| Error::new(path.span(), format!("{}: {}", resolved.display(), e)))?;
+    let flow = parse_file(&resolved, &source).map_err(|e| Error::new(path.span(), e))?;
+    // Ties the expansion to the file so that cargo rebuilds when it changes.
+    let resolved = resolved.to_string_lossy();
+    let track = quote::quote! {
+        const _: &str = ::core::include_str!(#resolved);
+    };
+    Ok((flow, track))
+}
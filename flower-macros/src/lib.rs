@@ -1,14 +1,29 @@
+mod include;
+
 use flower_parser::Flow;
 use quote::quote;
-use syn::parse_macro_input;
+use syn::{parse_macro_input, LitStr};
 
 #[proc_macro]
 pub fn flow(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let flow = parse_macro_input!(input as Flow);
     log::debug!("{:?}", flow);
-    /* (quote! {
-        #flow
-    })
-    .into() */
-    (quote! {}).into()
+    flower_codegen::expand(&flow, &Default::default()).into()
+}
+
+#[proc_macro]
+pub fn include_flow(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match include::load(&path) {
+        Ok((flow, track)) => {
+            log::debug!("{:?}", flow);
+            let expanded = flower_codegen::expand(&flow, &Default::default());
+            quote! {
+                #track
+                #expanded
+            }
+            .into()
+        }
+        Err(e) => e.to_compile_error().into(),
+    }
 }
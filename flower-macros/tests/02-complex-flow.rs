@@ -1,4 +1,32 @@
 use flower_macros::flow;
+use flower_runtime::{AsyncState, Context, Control, LocalBoxFuture};
+
+struct A;
+struct B;
+struct C;
+struct P;
+struct Q;
+struct R;
+struct S;
+struct T;
+
+impl AsyncState for Q {
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::Continue })
+    }
+}
+
+impl AsyncState for R {
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::Continue })
+    }
+}
+
+impl From<T> for R {
+    fn from(_: T) -> Self {
+        R
+    }
+}
 
 flow! {
     resource: [
@@ -16,8 +44,8 @@ flow! {
         T
     ]
     transition: [
-        A >--> B,
-        B >- T -> C
+        P >--> Q,
+        Q >- T -> R
     ]
     overlay: [
         R ^ S,
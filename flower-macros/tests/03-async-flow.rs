@@ -0,0 +1,75 @@
+use flower_macros::flow;
+use flower_runtime::executor::block_on;
+use flower_runtime::{AsyncState, Context, Control, Driver, LocalBoxFuture, Resources};
+
+struct Assets(Vec<&'static str>);
+struct Save(u32);
+
+struct Loading;
+struct Loaded;
+struct Title(bool);
+struct Pause;
+
+impl AsyncState for Loading {
+    fn update<'a>(&'a mut self, cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async move {
+            cx.get_mut::<Assets>().0.push("title.png");
+            Control::transition(Loaded)
+        })
+    }
+}
+
+impl From<Loaded> for Title {
+    fn from(_: Loaded) -> Self {
+        Title(false)
+    }
+}
+
+impl AsyncState for Title {
+    fn on_enter<'a>(&'a mut self, cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+        Box::pin(async move { assert_eq!(cx.get::<Assets>().0, ["title.png"]) })
+    }
+
+    fn update<'a>(&'a mut self, cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async move {
+            if self.0 {
+                return Control::Continue;
+            }
+            self.0 = true;
+            cx.get_mut::<Save>().0 += 1;
+            Control::push(Pause)
+        })
+    }
+}
+
+impl AsyncState for Pause {
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::Pop })
+    }
+}
+
+flow! {
+    resource: [Assets, Save]
+    state: [Loading, Title, Pause]
+    intermediate: [Loaded]
+    reference: [
+        Loading -< mut Assets,
+        Title -< Assets,
+        Title -< mut Save,
+    ]
+    transition: [Loading >- Loaded -> Title]
+    overlay: [Title ^ Pause]
+}
+
+fn main() {
+    let resources = Resources::new().with(Assets(Vec::new())).with(Save(0));
+    let mut driver = Driver::new(flow(), resources, Loading);
+    block_on(async {
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Title"]);
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Title", "Pause"]);
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Title"]);
+    });
+}
@@ -0,0 +1,51 @@
+use flower_macros::flow;
+use flower_runtime::executor::block_on;
+use flower_runtime::{AsyncState, Driver, ManualClock, Resources};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Splash;
+#[derive(Default)]
+struct Title;
+#[derive(Default)]
+struct Idle;
+struct Demo;
+
+impl AsyncState for Splash {}
+impl AsyncState for Title {}
+impl AsyncState for Demo {}
+
+impl From<Idle> for Demo {
+    fn from(_: Idle) -> Self {
+        Demo
+    }
+}
+
+flow! {
+    resource: []
+    state: [Splash, Title, Demo]
+    intermediate: [Idle]
+    reference: []
+    transition: [
+        Splash >--> Title after 3s,
+        Title >- Idle -> Demo after 1.5s,
+    ]
+    overlay: []
+}
+
+fn main() {
+    let clock = ManualClock::new();
+    let mut driver = Driver::new(flow(), Resources::new(), Splash).with_clock(clock.clone());
+    block_on(async {
+        driver.step().await.unwrap();
+        clock.advance(Duration::from_secs(3));
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Title"]);
+        clock.advance(Duration::from_millis(1499));
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Title"]);
+        clock.advance(Duration::from_millis(1));
+        driver.step().await.unwrap();
+        assert_eq!(driver.stack(), ["Demo"]);
+    });
+}
@@ -0,0 +1,98 @@
+use flower_macros::flow;
+use flower_parser::{Flow, RawFlow};
+use flower_runtime::executor::block_on;
+use flower_runtime::{AsyncState, Context, Control, Driver, LocalBoxFuture, Resources, Response};
+use std::any::Any;
+use std::str::FromStr;
+
+struct Escape;
+
+struct Title;
+struct Start;
+struct Game;
+struct Pause;
+
+impl AsyncState for Title {
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::transition(Start) })
+    }
+}
+
+impl From<Start> for Game {
+    fn from(_: Start) -> Self {
+        Game
+    }
+}
+
+impl AsyncState for Game {
+    fn on_event<'a>(
+        &'a mut self,
+        _cx: Context<'a>,
+        event: &'a dyn Any,
+    ) -> LocalBoxFuture<'a, Response> {
+        Box::pin(async move {
+            if event.is::<Escape>() {
+                Response::Consume(Control::push(Pause))
+            } else {
+                Response::Ignore
+            }
+        })
+    }
+}
+
+impl AsyncState for Pause {
+    fn on_event<'a>(
+        &'a mut self,
+        _cx: Context<'a>,
+        event: &'a dyn Any,
+    ) -> LocalBoxFuture<'a, Response> {
+        Box::pin(async move {
+            if event.is::<Escape>() {
+                Response::Consume(Control::Pop)
+            } else {
+                Response::Ignore
+            }
+        })
+    }
+}
+
+flow! {
+    resource: []
+    state: [Title, Game, Pause]
+    intermediate: [Start]
+    reference: []
+    transition: [Title >- Start -> Game]
+    overlay: [Game ^ Pause]
+}
+
+const FLOW_STR: &str = r#"
+resource: []
+state: [Title, Game, Pause]
+intermediate: [Start]
+reference: []
+transition: [Title >- Start -> Game]
+overlay: [Game ^ Pause]
+"#;
+
+fn main() {
+    let mut driver = Driver::new(flow(), Resources::new(), Title).recording();
+    block_on(async {
+        driver.step().await.unwrap();
+        driver.send(Escape);
+        driver.send(Escape);
+        driver.send(Escape);
+        driver.dispatch().await.unwrap();
+    });
+    assert_eq!(driver.stack(), ["Game", "Pause"]);
+
+    let lines = driver.trace().to_json_lines();
+    assert_eq!(lines.lines().count(), 8);
+    let trace = flower_parser::trace::Trace::from_json_lines(&lines).unwrap();
+
+    let raw_flow = RawFlow::from(Flow::from_str(FLOW_STR).unwrap());
+    let replayed = trace.replay(&raw_flow).unwrap();
+    assert_eq!(replayed.stack(), ["Game", "Pause"]);
+
+    let changed = RawFlow::from(Flow::from_str(&FLOW_STR.replace("Game ^ Pause", "")).unwrap());
+    assert!(trace.replay(&changed).is_err());
+}
@@ -0,0 +1,62 @@
+use flower_macros::flow;
+use flower_parser::coverage::{process_report, CoverageReport};
+use flower_runtime::executor::block_on;
+use flower_runtime::{AsyncState, Context, Control, Driver, LocalBoxFuture, Resources};
+use std::env;
+use std::fs;
+
+struct Title;
+struct Start;
+struct Game;
+struct Pause;
+
+impl AsyncState for Title {
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::transition(Start) })
+    }
+}
+
+impl From<Start> for Game {
+    fn from(_: Start) -> Self {
+        Game
+    }
+}
+
+impl AsyncState for Game {}
+
+impl AsyncState for Pause {}
+
+flow! {
+    resource: []
+    state: [Title, Game, Pause]
+    intermediate: [Start]
+    reference: []
+    transition: [Title >- Start -> Game, Game >--> Title]
+    overlay: [Game ^ Pause]
+}
+
+fn main() {
+    let path = env::temp_dir().join(format!("flower-coverage-{}.json", std::process::id()));
+    env::set_var("FLOWER_COVERAGE", &path);
+
+    // A part left behind by an earlier process with the same pid.
+    let mut stale = CoverageReport::default();
+    let coverage = stale.flows.entry(module_path!().to_string()).or_default();
+    coverage.states.insert("Title".to_string(), 5);
+    let part = process_report(&path, std::process::id());
+    fs::write(&part, serde_json::to_string(&stale).unwrap()).unwrap();
+
+    for _ in 0..2 {
+        let mut driver = Driver::new(flow(), Resources::new(), Title);
+        block_on(driver.step()).unwrap();
+    }
+
+    let report = CoverageReport::read(&path).unwrap();
+    fs::remove_file(&part).unwrap();
+    let coverage = &report.flows[module_path!()];
+    assert_eq!(coverage.states["Title"], 2);
+    assert_eq!(coverage.states["Game"], 2);
+    assert_eq!(coverage.transitions["Title >- Start -> Game"], 2);
+    assert!(!coverage.transitions.contains_key("Game >--> Title"));
+    assert!(coverage.overlays.is_empty());
+}
@@ -0,0 +1,74 @@
+use flower_macros::flow;
+use flower_parser::{Flow, RawFlow};
+use flower_runtime::{AsyncState, FlowMeta, OverlayMeta, ReferenceMeta, TransitionMeta};
+use std::str::FromStr;
+
+struct Save;
+
+#[derive(Default)]
+struct Title;
+struct Start;
+struct Game;
+struct Pause;
+
+impl AsyncState for Title {}
+impl AsyncState for Game {}
+impl AsyncState for Pause {}
+
+impl From<Start> for Game {
+    fn from(_: Start) -> Self {
+        Game
+    }
+}
+
+flow! {
+    resource: [Save]
+    state: [Title, Game, Pause]
+    intermediate: [Start]
+    reference: [Game -< mut Save, Pause -< Save]
+    transition: [Title >- Start -> Game, Game >--> Title after 2s]
+    overlay: [Game ^ Pause]
+}
+
+const FLOW_STR: &str = r#"
+resource: [Save]
+state: [Title, Game, Pause]
+intermediate: [Start]
+reference: [Game -< mut Save, Pause -< Save]
+transition: [Title >- Start -> Game, Game >--> Title after 2s]
+overlay: [Game ^ Pause]
+"#;
+
+const STATES: &[&str] = FLOW.states;
+
+fn main() {
+    assert_eq!(STATES, ["Title", "Game", "Pause"]);
+    assert_eq!(
+        FLOW.references_of("Game").collect::<Vec<_>>(),
+        [&ReferenceMeta {
+            state: "Game",
+            mutable: true,
+            resource: "Save",
+        }]
+    );
+    assert_eq!(
+        FLOW.transitions_from("Game").collect::<Vec<_>>(),
+        [&TransitionMeta {
+            from: "Game",
+            intermediate: None,
+            to: "Title",
+            after_ms: Some(2000),
+        }]
+    );
+    assert_eq!(
+        FLOW.overlays_on("Game").collect::<Vec<_>>(),
+        [&OverlayMeta {
+            back: "Game",
+            front: "Pause",
+        }]
+    );
+
+    let meta: &FlowMeta = &FLOW;
+    let raw_flow = RawFlow::from(Flow::from_str(FLOW_STR).unwrap());
+    assert_eq!(RawFlow::from(meta), raw_flow);
+}
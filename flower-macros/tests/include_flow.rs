@@ -0,0 +1,39 @@
+use std::any::TypeId;
+
+use flower_runtime::AsyncState;
+
+struct Save;
+
+struct Title;
+struct Start;
+struct Game;
+struct Pause;
+
+impl AsyncState for Title {}
+impl AsyncState for Game {}
+impl AsyncState for Pause {}
+
+impl From<Start> for Game {
+    fn from(_: Start) -> Self {
+        Game
+    }
+}
+
+mod text {
+    use super::*;
+
+    flower_macros::include_flow!("tests/flows/game.flow");
+}
+
+mod json {
+    use super::*;
+
+    flower_macros::include_flow!("tests/flows/game.json");
+}
+
+#[test]
+fn text_and_json_expand_alike() {
+    assert_eq!(text::FLOW, json::FLOW);
+    assert_eq!(text::FLOW.states, ["Title", "Game", "Pause"]);
+    assert!(text::flow().conflicts(TypeId::of::<Game>(), TypeId::of::<Pause>()));
+}
@@ -5,5 +5,11 @@ mod parse_test {
         let t = trybuild::TestCases::new();
         t.pass("tests/01-empty-flow.rs");
         t.pass("tests/02-complex-flow.rs");
+        t.pass("tests/03-async-flow.rs");
+        t.pass("tests/04-timed-flow.rs");
+        t.pass("tests/05-trace.rs");
+        t.pass("tests/06-coverage.rs");
+        t.pass("tests/07-flow-meta.rs");
+        t.compile_fail("tests/ui/*.rs");
     }
 }
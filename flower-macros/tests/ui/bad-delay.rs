@@ -0,0 +1,20 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+#[derive(Default)]
+struct Title;
+struct Game;
+
+impl AsyncState for Title {}
+impl AsyncState for Game {}
+
+flow! {
+    resource: []
+    state: [Title, Game]
+    intermediate: []
+    reference: []
+    transition: [Title >--> Game after 3m]
+    overlay: []
+}
+
+fn main() {}
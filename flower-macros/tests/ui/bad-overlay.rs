@@ -0,0 +1,19 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+struct Game;
+struct Pause;
+
+impl AsyncState for Game {}
+impl AsyncState for Pause {}
+
+flow! {
+    resource: []
+    state: [Game, Pause]
+    intermediate: []
+    reference: []
+    transition: []
+    overlay: [Game + Pause]
+}
+
+fn main() {}
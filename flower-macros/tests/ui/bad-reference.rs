@@ -0,0 +1,18 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+struct Save;
+struct Game;
+
+impl AsyncState for Game {}
+
+flow! {
+    resource: [Save]
+    state: [Game]
+    intermediate: []
+    reference: [Game <- Save]
+    transition: []
+    overlay: []
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+struct Title;
+struct Game;
+
+impl AsyncState for Title {}
+impl AsyncState for Game {}
+
+flow! {
+    resource: []
+    state: [Title, Game]
+    intermediate: []
+    reference: []
+    transition: [Title -> Game]
+    overlay: []
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+use flower_macros::include_flow;
+
+include_flow!("../../../../flower-macros/tests/ui/flows/bad.flow");
+
+fn main() {}
@@ -0,0 +1,5 @@
+use flower_macros::include_flow;
+
+include_flow!("flows/missing.flow");
+
+fn main() {}
@@ -0,0 +1,20 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+struct Title;
+struct Start;
+struct Game;
+
+impl AsyncState for Title {}
+impl AsyncState for Game {}
+
+flow! {
+    resource: []
+    state: [Title, Game]
+    intermediate: [Start]
+    reference: []
+    transition: [Title >- Start -> Game]
+    overlay: []
+}
+
+fn main() {}
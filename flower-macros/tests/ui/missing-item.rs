@@ -0,0 +1,11 @@
+use flower_macros::flow;
+
+flow! {
+    resource: []
+    state: []
+    intermediate: []
+    reference: []
+    transition: []
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use flower_macros::flow;
+use flower_runtime::AsyncState;
+
+struct Splash;
+struct Title;
+
+impl AsyncState for Splash {}
+impl AsyncState for Title {}
+
+flow! {
+    resource: []
+    state: [Splash, Title]
+    intermediate: []
+    reference: []
+    transition: [Splash >--> Title after 3s]
+    overlay: []
+}
+
+fn main() {}
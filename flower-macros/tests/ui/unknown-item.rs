@@ -0,0 +1,12 @@
+use flower_macros::flow;
+
+flow! {
+    resource: []
+    states: []
+    intermediate: []
+    reference: []
+    transition: []
+    overlay: []
+}
+
+fn main() {}
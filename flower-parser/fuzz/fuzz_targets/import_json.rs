@@ -0,0 +1,16 @@
+#![no_main]
+
+use std::str;
+
+use flower_parser::schema::{self, Mode};
+use flower_parser::Flow;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let raw = str::from_utf8(data)
+        .ok()
+        .and_then(|s| schema::from_str(s, Mode::Lenient).ok());
+    if let Some(raw) = raw {
+        let _ = Flow::try_from(raw);
+    }
+});
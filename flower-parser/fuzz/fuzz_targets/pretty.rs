@@ -0,0 +1,23 @@
+#![no_main]
+
+use std::str::{self, FromStr};
+
+use flower_parser::schema::{self, Mode};
+use flower_parser::{Flow, RawFlow};
+use libfuzzer_sys::fuzz_target;
+
+// Any flow that imports must print to text that parses back to the same flow.
+fuzz_target!(|data: &[u8]| {
+    let raw = match str::from_utf8(data).map(|s| schema::from_str(s, Mode::Lenient)) {
+        Ok(Ok(raw)) => raw,
+        _ => return,
+    };
+    let canonical = match Flow::try_from(raw) {
+        Ok(flow) => RawFlow::from(flow),
+        Err(_) => return,
+    };
+    let text = canonical.to_string();
+    let reparsed = Flow::from_str(&text)
+        .unwrap_or_else(|e| panic!("pretty-printed flow does not parse: {}\n{}", e, text));
+    assert_eq!(RawFlow::from(reparsed), canonical, "{}", text);
+});
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Rebuilds the seed corpus from the test fixtures.
+set -e
+cd "$(dirname "$0")"
+root=../..
+mkdir -p corpus/parse_flow corpus/import_json corpus/pretty
+for f in $root/flower-codegen/tests/flows/*.flow; do
+    cp "$f" corpus/parse_flow/codegen-"$(basename "$f")"
+done
+cp $root/flower-cli/tests/fixtures/game.flow corpus/parse_flow/cli-game.flow
+cp $root/flower-macros/tests/flows/game.flow corpus/parse_flow/macros-game.flow
+for target in import_json pretty; do
+    for f in $root/flower-codegen/tests/snapshots/*.json; do
+        cp "$f" corpus/$target/codegen-"$(basename "$f")"
+    done
+    cp $root/flower-macros/tests/flows/game.json corpus/$target/macros-game.json
+done
@@ -0,0 +1,210 @@
+use proptest::collection::{btree_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+use super::raw_ast::{
+    RawFlow, RawIntermediate, RawLayoutNode, RawOverlay, RawReference, RawResource, RawState,
+    RawTransition,
+};
+
+fn ident() -> impl Strategy<Value = String> {
+    "[A-Z][A-Za-z0-9]{0,5}".prop_filter("keyword", |s| s != "Self")
+}
+
+fn module() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9]{0,4}".prop_filter("keyword", |s| {
+        syn::parse_str::<syn::Ident>(s).is_ok() && s != "crate" && s != "self" && s != "super"
+    })
+}
+
+// Type paths as the pretty-printer writes them, e.g. `game::Level<Save, 1>`.
+fn type_path() -> impl Strategy<Value = String> {
+    let argument = prop_oneof![ident(), (0u8..10).prop_map(|n| n.to_string())];
+    (vec(module(), 0..3), ident(), vec(argument, 0..3)).prop_map(|(modules, name, arguments)| {
+        let mut path: Vec<String> = modules;
+        path.push(name);
+        let mut s = path.join("::");
+        if !arguments.is_empty() {
+            s += &format!("<{}>", arguments.join(", "));
+        }
+        s
+    })
+}
+
+fn unique_types(max: usize) -> impl Strategy<Value = Vec<String>> {
+    vec(type_path(), 0..max).prop_map(|mut types| {
+        let mut seen = std::collections::HashSet::new();
+        types.retain(|t| seen.insert(t.clone()));
+        types
+    })
+}
+
+fn layout_node() -> impl Strategy<Value = RawLayoutNode> {
+    (
+        -1000..1000,
+        -1000..1000,
+        option::of("[ -~]{0,8}"),
+        any::<bool>(),
+    )
+        .prop_map(|(x, y, colour, collapsed)| RawLayoutNode {
+            x,
+            y,
+            colour,
+            collapsed,
+        })
+}
+
+// References, transitions and overlays may also mention a few undeclared names, which the
+// parser accepts with a warning.
+pub fn raw_flow() -> impl Strategy<Value = RawFlow> {
+    (
+        unique_types(4),
+        unique_types(6),
+        unique_types(3),
+        unique_types(2),
+    )
+        .prop_flat_map(|(resources, states, intermediates, undeclared)| {
+            let declared: Vec<&String> = [&resources, &states, &intermediates]
+                .into_iter()
+                .flatten()
+                .collect();
+            let undeclared: Vec<String> = undeclared
+                .into_iter()
+                .filter(|name| !declared.contains(&name))
+                .collect();
+            let mentionable =
+                |v: &Vec<String>| -> Vec<String> { v.iter().chain(&undeclared).cloned().collect() };
+            let pick = |v: &Vec<String>| select(v.clone());
+            let (mentioned_resources, mentioned_states, mentioned_intermediates) = (
+                mentionable(&resources),
+                mentionable(&states),
+                mentionable(&intermediates),
+            );
+            let references = if mentioned_states.is_empty() || mentioned_resources.is_empty() {
+                Just(Vec::new()).boxed()
+            } else {
+                vec(
+                    (
+                        pick(&mentioned_states),
+                        any::<bool>(),
+                        pick(&mentioned_resources),
+                    )
+                        .prop_map(|(state, mutable, resource)| RawReference {
+                            state: RawState(state),
+                            mutable,
+                            resource: RawResource(resource),
+                        }),
+                    0..5,
+                )
+                .boxed()
+            };
+            let (transitions, overlays) = if mentioned_states.is_empty() {
+                (Just(Vec::new()).boxed(), Just(Vec::new()).boxed())
+            } else {
+                let intermediate = if mentioned_intermediates.is_empty() {
+                    Just(None).boxed()
+                } else {
+                    option::of(pick(&mentioned_intermediates)).boxed()
+                };
+                let transitions = vec(
+                    (
+                        pick(&mentioned_states),
+                        intermediate,
+                        pick(&mentioned_states),
+                        option::of(prop_oneof![1u64..5000, (1u64..10).prop_map(|s| s * 1000)]),
+                    )
+                        .prop_map(|(from, intermediate, to, after_ms)| {
+                            RawTransition {
+                                from: RawState(from),
+                                intermediate: intermediate.map(RawIntermediate),
+                                to: RawState(to),
+                                after_ms,
+                            }
+                        }),
+                    0..6,
+                )
+                .boxed();
+                let overlays = vec(
+                    (pick(&mentioned_states), pick(&mentioned_states)).prop_map(|(back, front)| {
+                        RawOverlay {
+                            back: RawState(back),
+                            front: RawState(front),
+                        }
+                    }),
+                    0..3,
+                )
+                .boxed();
+                (transitions, overlays)
+            };
+            let names: Vec<String> = [&resources, &states, &intermediates]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let layout = if names.is_empty() {
+                Just(None).boxed()
+            } else {
+                option::of(btree_map(select(names), layout_node(), 0..4)).boxed()
+            };
+            (
+                Just(resources),
+                Just(states),
+                Just(intermediates),
+                references,
+                transitions,
+                overlays,
+                layout,
+            )
+        })
+        .prop_map(
+            |(resources, states, intermediates, references, transitions, overlays, layout)| {
+                RawFlow {
+                    resources: resources.into_iter().map(RawResource).collect(),
+                    states: states.into_iter().map(RawState).collect(),
+                    intermediates: intermediates.into_iter().map(RawIntermediate).collect(),
+                    references,
+                    transitions,
+                    overlays,
+                    layout,
+                }
+            },
+        )
+}
+
+#[cfg(test)]
+mod round_trip_test {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+    use quote::ToTokens;
+
+    use super::raw_flow;
+    use crate::{flow2json, json2flow, Flow, RawFlow};
+
+    proptest! {
+        // text -> Flow -> RawFlow -> JSON -> RawFlow -> Flow -> text
+        #[test]
+        fn text_json_text_is_stable(flow in raw_flow()) {
+            let text = flow.to_string();
+            let parsed = RawFlow::from(Flow::from_str(&text).unwrap());
+            prop_assert_eq!(parsed.to_string(), text.clone());
+
+            let json = serde_json::to_string(&parsed).unwrap();
+            let from_json: RawFlow = serde_json::from_str(&json).unwrap();
+            prop_assert_eq!(&from_json, &parsed);
+
+            let tokens = Flow::try_from(from_json).unwrap().into_token_stream().to_string();
+            let reparsed = RawFlow::from(Flow::from_str(&tokens).unwrap());
+            prop_assert_eq!(&reparsed, &parsed);
+            prop_assert_eq!(reparsed.to_string(), text);
+        }
+
+        #[test]
+        fn flow2json_and_json2flow_agree(flow in raw_flow()) {
+            let json = flow2json(flow.to_string());
+            let text = json2flow(json.clone());
+            prop_assert_eq!(flow2json(text), json);
+        }
+    }
+}
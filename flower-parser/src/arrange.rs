@@ -0,0 +1,602 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::raw_ast::{RawFlow, RawLayoutNode};
+
+// Distance between layers, and between neighbours within a layer.
+const COLUMN_WIDTH: i32 = 200;
+const ROW_HEIGHT: i32 = 100;
+// Barycentre sweeps when ordering the layers, alternating down and up.
+const SWEEPS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Resource,
+    State,
+    Intermediate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+// A node centred on `x`, `y`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS), ts(rename = "ArrangedNode"))]
+pub struct Node {
+    pub name: String,
+    pub kind: NodeKind,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    Transition,
+    Reference,
+    Overlay,
+}
+
+// A polyline from the centre of `from` to the centre of `to`. Transitions bend around
+// the layers they cross and pass through their intermediate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS), ts(rename = "ArrangedEdge"))]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub from: String,
+    pub to: String,
+    pub points: Vec<Point>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Arrangement {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Arrangement {
+    // Node positions in the form the editor saves with the flow.
+    pub fn layout(&self) -> BTreeMap<String, RawLayoutNode> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let position = RawLayoutNode {
+                    x: node.x,
+                    y: node.y,
+                    colour: None,
+                    collapsed: false,
+                };
+                (node.name.clone(), position)
+            })
+            .collect()
+    }
+}
+
+// A transition between two laid-out states, oriented so that the layer graph is acyclic.
+struct Link {
+    transition: usize,
+    from: usize,
+    to: usize,
+    reversed: bool,
+    // A transition through an intermediate leaves a layer free for it.
+    span: usize,
+}
+
+fn unique<'a>(names: impl Iterator<Item = &'a String>) -> Vec<&'a str> {
+    let mut unique: Vec<&str> = Vec::new();
+    for name in names {
+        if !unique.contains(&name.as_str()) {
+            unique.push(name);
+        }
+    }
+    unique
+}
+
+// Overlay fronts that no transition enters are stacked under their back instead of
+// taking a place of their own. Returns the back of each stacked state.
+fn stacks(flow: &RawFlow, states: &HashMap<&str, usize>) -> Vec<Option<usize>> {
+    let mut entered = vec![false; states.len()];
+    for t in &flow.transitions {
+        if let (Some(from), Some(to)) = (states.get(t.from.0.as_str()), states.get(t.to.0.as_str()))
+        {
+            entered[*to] |= from != to;
+        }
+    }
+    let mut backs: Vec<Option<usize>> = vec![None; states.len()];
+    for o in &flow.overlays {
+        let (back, front) = match (
+            states.get(o.back.0.as_str()),
+            states.get(o.front.0.as_str()),
+        ) {
+            (Some(back), Some(front)) => (*back, *front),
+            _ => continue,
+        };
+        if entered[front] || backs[front].is_some() {
+            continue;
+        }
+        let mut root = Some(back);
+        while let Some(state) = root {
+            if state == front {
+                break;
+            }
+            root = backs[state];
+        }
+        if root.is_none() {
+            backs[front] = Some(back);
+        }
+    }
+    backs
+}
+
+// Reverses the transitions that close a cycle, found by a depth-first search that starts
+// from the states no transition enters, in declaration order.
+fn break_cycles(count: usize, links: &mut [Link]) {
+    let mut outgoing = vec![Vec::new(); count];
+    let mut entered = vec![false; count];
+    for (index, link) in links.iter().enumerate() {
+        outgoing[link.from].push(index);
+        entered[link.to] = true;
+    }
+    let roots = (0..count)
+        .filter(|s| !entered[*s])
+        .chain((0..count).filter(|s| entered[*s]));
+
+    // 0: unvisited, 1: on the search path, 2: finished.
+    let mut mark = vec![0u8; count];
+    for root in roots {
+        if mark[root] != 0 {
+            continue;
+        }
+        mark[root] = 1;
+        let mut path = vec![(root, 0)];
+        while let Some((state, next)) = path.last_mut() {
+            match outgoing[*state].get(*next) {
+                Some(&link) => {
+                    *next += 1;
+                    let to = links[link].to;
+                    match mark[to] {
+                        0 => {
+                            mark[to] = 1;
+                            path.push((to, 0));
+                        }
+                        1 => links[link].reversed = true,
+                        _ => {}
+                    }
+                }
+                None => {
+                    mark[*state] = 2;
+                    path.pop();
+                }
+            }
+        }
+    }
+    for link in links.iter_mut().filter(|l| l.reversed) {
+        std::mem::swap(&mut link.from, &mut link.to);
+    }
+}
+
+// Longest-path layering: every state sits at least `span` layers after its predecessors.
+fn assign_layers(count: usize, links: &[Link]) -> Vec<usize> {
+    let mut incoming = vec![0; count];
+    for link in links {
+        incoming[link.to] += 1;
+    }
+    let mut layer = vec![0; count];
+    let mut ready: Vec<usize> = (0..count).rev().filter(|s| incoming[*s] == 0).collect();
+    while let Some(state) = ready.pop() {
+        for link in links.iter().filter(|l| l.from == state) {
+            layer[link.to] = layer[link.to].max(layer[state] + link.span);
+            incoming[link.to] -= 1;
+            if incoming[link.to] == 0 {
+                let at = ready.partition_point(|s| *s > link.to);
+                ready.insert(at, link.to);
+            }
+        }
+    }
+    layer
+}
+
+fn crossings(upper: &[usize], lower: &[usize], segments: &[(usize, usize)]) -> usize {
+    let position = |layer: &[usize], v: usize| layer.iter().position(|u| *u == v);
+    let between: Vec<(usize, usize)> = segments
+        .iter()
+        .filter_map(|(a, b)| Some((position(upper, *a)?, position(lower, *b)?)))
+        .collect();
+    let mut count = 0;
+    for (i, (a1, b1)) in between.iter().enumerate() {
+        for (a2, b2) in &between[i + 1..] {
+            if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn total_crossings(layers: &[Vec<usize>], segments: &[(usize, usize)]) -> usize {
+    layers
+        .windows(2)
+        .map(|pair| crossings(&pair[0], &pair[1], segments))
+        .sum()
+}
+
+// Orders each layer by the mean position of its neighbours in the layer before it,
+// sweeping down then up, and keeps the order with the fewest crossings.
+fn order(mut layers: Vec<Vec<usize>>, segments: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut best = layers.clone();
+    let mut fewest = total_crossings(&layers, segments);
+    for sweep in 0..SWEEPS {
+        let down = sweep % 2 == 0;
+        let indices: Vec<usize> = if down {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        for index in indices {
+            let fixed = if down {
+                &layers[index - 1]
+            } else {
+                &layers[index + 1]
+            };
+            let position: HashMap<usize, usize> =
+                fixed.iter().enumerate().map(|(p, v)| (*v, p)).collect();
+            let mut keyed: Vec<(f64, usize, usize)> = layers[index]
+                .iter()
+                .enumerate()
+                .map(|(current, v)| {
+                    let neighbours: Vec<usize> = segments
+                        .iter()
+                        .filter_map(|(a, b)| match down {
+                            true if b == v => position.get(a).copied(),
+                            false if a == v => position.get(b).copied(),
+                            _ => None,
+                        })
+                        .collect();
+                    let key = if neighbours.is_empty() {
+                        current as f64
+                    } else {
+                        neighbours.iter().sum::<usize>() as f64 / neighbours.len() as f64
+                    };
+                    (key, current, *v)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            layers[index] = keyed.into_iter().map(|(_, _, v)| v).collect();
+        }
+        let count = total_crossings(&layers, segments);
+        if count < fewest {
+            fewest = count;
+            best = layers.clone();
+        }
+    }
+    best
+}
+
+// Lays the states out in layers along their transitions, left to right, with resources and
+// intermediates that are on no laid-out transition in a column to the left. The result only
+// depends on the flow, including the order of its declarations.
+pub fn arrange(flow: &RawFlow) -> Arrangement {
+    let states = unique(flow.states.iter().map(|s| &s.0));
+    let index: HashMap<&str, usize> = states.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+    let backs = stacks(flow, &index);
+
+    let mut links: Vec<Link> = flow
+        .transitions
+        .iter()
+        .enumerate()
+        .filter_map(|(transition, t)| {
+            let from = *index.get(t.from.0.as_str())?;
+            let to = *index.get(t.to.0.as_str())?;
+            let laid_out = from != to && backs[from].is_none() && backs[to].is_none();
+            laid_out.then(|| Link {
+                transition,
+                from,
+                to,
+                reversed: false,
+                span: if t.intermediate.is_some() { 2 } else { 1 },
+            })
+        })
+        .collect();
+    break_cycles(states.len(), &mut links);
+    let mut layer = assign_layers(states.len(), &links);
+    for (state, back) in backs.iter().enumerate() {
+        let mut root = state;
+        while let Some(back) = backs[root] {
+            root = back;
+        }
+        if back.is_some() {
+            layer[state] = layer[root];
+        }
+    }
+
+    // Vertices are the states, then one dummy for each layer a transition crosses.
+    let mut vertex_layer = layer.clone();
+    let mut chains: Vec<(usize, Vec<usize>)> = Vec::new();
+    let mut segments = Vec::new();
+    for link in &links {
+        let mut chain = vec![link.from];
+        for l in layer[link.from] + 1..layer[link.to] {
+            vertex_layer.push(l);
+            chain.push(vertex_layer.len() - 1);
+        }
+        chain.push(link.to);
+        segments.extend(chain.windows(2).map(|pair| (pair[0], pair[1])));
+        if link.reversed {
+            chain.reverse();
+        }
+        chains.push((link.transition, chain));
+    }
+
+    let depth = vertex_layer.iter().max().map_or(0, |l| l + 1);
+    let mut layers = vec![Vec::new(); depth];
+    for (vertex, l) in vertex_layer.iter().enumerate() {
+        if vertex >= states.len() || backs[vertex].is_none() {
+            layers[*l].push(vertex);
+        }
+    }
+    let layers: Vec<Vec<usize>> = order(layers, &segments)
+        .into_iter()
+        .map(|layer| {
+            let mut stacked = Vec::new();
+            for vertex in layer {
+                stack(vertex, &backs, flow, &index, &mut stacked);
+            }
+            stacked
+        })
+        .collect();
+
+    let rows = layers.iter().map(Vec::len).max().unwrap_or(0) as i32;
+    let mut position = vec![Point { x: 0, y: 0 }; vertex_layer.len()];
+    for (l, layer) in layers.iter().enumerate() {
+        let offset = (rows - layer.len() as i32) * ROW_HEIGHT / 2;
+        for (row, vertex) in layer.iter().enumerate() {
+            position[*vertex] = Point {
+                x: (l as i32 + 1) * COLUMN_WIDTH,
+                y: offset + row as i32 * ROW_HEIGHT,
+            };
+        }
+    }
+
+    let mut arrangement = Arrangement::default();
+    let mut node = |name: &str, kind, at: Point| {
+        arrangement.nodes.push(Node {
+            name: name.to_string(),
+            kind,
+            x: at.x,
+            y: at.y,
+        })
+    };
+    for (state, name) in states.iter().enumerate() {
+        node(name, NodeKind::State, position[state]);
+    }
+    // Each intermediate sits on the first laid-out transition through it.
+    let mut intermediates: HashMap<&str, Point> = HashMap::new();
+    let mut owners = HashSet::new();
+    for (transition, chain) in &chains {
+        if let Some(i) = &flow.transitions[*transition].intermediate {
+            if !intermediates.contains_key(i.0.as_str()) {
+                intermediates.insert(&i.0, position[chain[1]]);
+                owners.insert(*transition);
+            }
+        }
+    }
+    let mut side = 0;
+    let mut next_in_side_column = || {
+        side += 1;
+        Point {
+            x: 0,
+            y: (side - 1) * ROW_HEIGHT,
+        }
+    };
+    for resource in unique(flow.resources.iter().map(|r| &r.0)) {
+        node(resource, NodeKind::Resource, next_in_side_column());
+    }
+    for name in unique(flow.intermediates.iter().map(|i| &i.0)) {
+        let at = match intermediates.get(name) {
+            Some(at) => *at,
+            None => {
+                let at = next_in_side_column();
+                intermediates.insert(name, at);
+                at
+            }
+        };
+        node(name, NodeKind::Intermediate, at);
+    }
+    let at = |name: &str, nodes: &[Node]| {
+        nodes
+            .iter()
+            .find(|n| n.name == name)
+            .map(|n| Point { x: n.x, y: n.y })
+    };
+
+    let chains: HashMap<usize, &Vec<usize>> = chains.iter().map(|(t, c)| (*t, c)).collect();
+    for (transition, t) in flow.transitions.iter().enumerate() {
+        let points = match chains.get(&transition) {
+            Some(chain) if t.intermediate.is_none() || owners.contains(&transition) => {
+                chain.iter().map(|v| position[*v]).collect()
+            }
+            _ => {
+                let from = at(&t.from.0, &arrangement.nodes);
+                let to = at(&t.to.0, &arrangement.nodes);
+                let through = t
+                    .intermediate
+                    .as_ref()
+                    .and_then(|i| intermediates.get(i.0.as_str()).copied());
+                match (from, to) {
+                    (Some(from), Some(to)) => [Some(from), through, Some(to)]
+                        .into_iter()
+                        .flatten()
+                        .collect(),
+                    _ => continue,
+                }
+            }
+        };
+        arrangement.edges.push(Edge {
+            kind: EdgeKind::Transition,
+            from: t.from.0.clone(),
+            to: t.to.0.clone(),
+            points,
+        });
+    }
+    let pairs = flow
+        .references
+        .iter()
+        .map(|r| (EdgeKind::Reference, &r.state.0, &r.resource.0))
+        .chain(
+            flow.overlays
+                .iter()
+                .map(|o| (EdgeKind::Overlay, &o.back.0, &o.front.0)),
+        );
+    for (kind, from, to) in pairs {
+        if let (Some(a), Some(b)) = (at(from, &arrangement.nodes), at(to, &arrangement.nodes)) {
+            arrangement.edges.push(Edge {
+                kind,
+                from: from.clone(),
+                to: to.clone(),
+                points: vec![a, b],
+            });
+        }
+    }
+    arrangement
+}
+
+// Appends `vertex` and then, recursively, the states stacked on it in overlay order.
+fn stack(
+    vertex: usize,
+    backs: &[Option<usize>],
+    flow: &RawFlow,
+    index: &HashMap<&str, usize>,
+    out: &mut Vec<usize>,
+) {
+    out.push(vertex);
+    for o in &flow.overlays {
+        if index.get(o.back.0.as_str()) != Some(&vertex) {
+            continue;
+        }
+        if let Some(&front) = index.get(o.front.0.as_str()) {
+            if backs[front] == Some(vertex) && !out.contains(&front) {
+                stack(front, backs, flow, index, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod arrange_test {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::arbitrary::raw_flow;
+    use crate::Flow;
+
+    fn arranged(text: &str) -> Arrangement {
+        arrange(&Flow::from_str(text).unwrap().into())
+    }
+
+    fn node<'a>(arrangement: &'a Arrangement, name: &str) -> &'a Node {
+        arrangement.nodes.iter().find(|n| n.name == name).unwrap()
+    }
+
+    const GAME: &str = "resource: [Save, Audio] state: [Title, Game, Pause, GameOver] \
+        intermediate: [Start, Unused] reference: [Game -< mut Save] \
+        transition: [Title >- Start -> Game, Game >--> GameOver, GameOver >--> Title] \
+        overlay: [Game ^ Pause]";
+
+    #[test]
+    fn states_follow_the_transitions() {
+        let a = arranged(GAME);
+        let x = |name| node(&a, name).x;
+        assert_eq!(
+            [x("Title"), x("Start"), x("Game"), x("GameOver")],
+            [
+                COLUMN_WIDTH,
+                2 * COLUMN_WIDTH,
+                3 * COLUMN_WIDTH,
+                4 * COLUMN_WIDTH
+            ]
+        );
+        assert_eq!(node(&a, "Start").kind, NodeKind::Intermediate);
+
+        // The transition that closes the cycle still runs from `GameOver` to `Title`.
+        let back = a.edges.iter().find(|e| e.from == "GameOver").unwrap();
+        let ends = (back.points.first().unwrap(), back.points.last().unwrap());
+        assert_eq!((ends.0.x, ends.1.x), (4 * COLUMN_WIDTH, COLUMN_WIDTH));
+        assert_eq!(back.points.len(), 4);
+    }
+
+    #[test]
+    fn resources_and_overlays() {
+        let a = arranged(GAME);
+        let (game, pause) = (node(&a, "Game"), node(&a, "Pause"));
+        assert_eq!((pause.x, pause.y), (game.x, game.y + ROW_HEIGHT));
+        let side: Vec<_> = a.nodes.iter().filter(|n| n.x == 0).collect();
+        assert_eq!(
+            side.iter()
+                .map(|n| (n.name.as_str(), n.y))
+                .collect::<Vec<_>>(),
+            [
+                ("Save", 0),
+                ("Audio", ROW_HEIGHT),
+                ("Unused", 2 * ROW_HEIGHT)
+            ]
+        );
+        let reference = a
+            .edges
+            .iter()
+            .find(|e| e.kind == EdgeKind::Reference)
+            .unwrap();
+        assert_eq!(
+            reference.points,
+            [
+                Point {
+                    x: game.x,
+                    y: game.y
+                },
+                Point { x: 0, y: 0 }
+            ]
+        );
+        assert_eq!(a.layout()["Pause"].y, pause.y);
+    }
+
+    #[test]
+    fn crossings_are_removed() {
+        let a = arranged(
+            "resource: [] state: [A, B, C, D] intermediate: [] reference: [] \
+             transition: [A >--> D, B >--> C] overlay: []",
+        );
+        assert!(node(&a, "D").y < node(&a, "C").y);
+    }
+
+    #[test]
+    fn arrangement_is_deterministic() {
+        let text = "resource: [R] state: [A, B, C, D, E] intermediate: [N] \
+            reference: [C -< R] transition: [A >--> B, A >--> C, B >- N -> E, C >--> D, \
+            D >--> A, E >--> E, C >- N -> A] overlay: [B ^ D, D ^ B]";
+        let first = arranged(text);
+        for _ in 0..10 {
+            assert_eq!(arranged(text), first);
+        }
+        assert_eq!(first.nodes.len(), 7);
+    }
+
+    proptest! {
+        #[test]
+        fn nodes_never_overlap(flow in raw_flow()) {
+            let a = arrange(&flow);
+            let positions: HashSet<_> = a.nodes.iter().map(|n| (n.x, n.y)).collect();
+            prop_assert_eq!(positions.len(), a.nodes.len());
+            prop_assert!(a.edges.iter().all(|e| e.points.len() >= 2));
+        }
+    }
+}
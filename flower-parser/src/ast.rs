@@ -9,16 +9,19 @@ pub mod kw {
     custom_keyword!(reference);
     custom_keyword!(transition);
     custom_keyword!(overlay);
+    custom_keyword!(after);
+    custom_keyword!(layout);
 }
 
+use std::fmt;
 use std::str::FromStr;
 
-use proc_macro2::TokenStream;
+use proc_macro2::{Literal, Spacing, TokenStream, TokenTree};
 use syn::{
     custom_punctuation, parse2,
     punctuated::Punctuated,
-    token::{Bracket, Colon},
-    Token, TypePath,
+    token::{Brace, Bracket, Colon, Paren},
+    Ident, Lit, Token, TypePath,
 };
 custom_punctuation!(SubLt, -<);
 custom_punctuation!(GtSub, >-);
@@ -47,6 +50,14 @@ pub struct Transition {
     pub intermediate: Option<Intermediate>,
     pub rarrow_token: Token![->],
     pub to: State,
+    pub delay: Option<Delay>,
+}
+
+#[derive(Debug)]
+pub struct Delay {
+    pub after_token: kw::after,
+    pub lit: Lit,
+    pub millis: u64,
 }
 
 #[derive(Debug)]
@@ -64,8 +75,39 @@ pub struct Item<K, T, P = Token![,]> {
     pub punct: Punctuated<T, P>,
 }
 
+#[derive(Debug)]
+pub struct LayoutField {
+    pub name: Ident,
+    pub colon_token: Colon,
+    pub value: Lit,
+}
+
+// `Title { x: 0, y: 40, colour: "#f392a3", collapsed: true }`: where the editor draws an
+// element. `x` and `y` are required.
+#[derive(Debug)]
+pub struct LayoutNode {
+    pub element: TypePath,
+    pub brace: Brace,
+    pub fields: Punctuated<LayoutField, Token![,]>,
+    pub x: i32,
+    pub y: i32,
+    pub colour: Option<String>,
+    pub collapsed: bool,
+}
+
+// `#[layout(...)]` before the items. It only matters to the editor; `flow!` ignores it.
+#[derive(Debug)]
+pub struct Layout {
+    pub pound_token: Token![#],
+    pub bracket: Bracket,
+    pub keyword: kw::layout,
+    pub paren: Paren,
+    pub punct: Punctuated<LayoutNode, Token![,]>,
+}
+
 #[derive(Debug)]
 pub struct Flow {
+    pub layout: Option<Layout>,
     pub resources: Item<kw::resource, Resource>,
     pub states: Item<kw::state, State>,
     pub intermediates: Item<kw::intermediate, Intermediate>,
@@ -74,17 +116,99 @@ pub struct Flow {
     pub overlays: Item<kw::overlay, Overlay>,
 }
 
+#[derive(Debug)]
 pub enum ParseError {
     LexError(proc_macro2::LexError),
     SynError(syn::Error),
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::LexError(e) => write!(f, "{}", e),
+            ParseError::SynError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 impl FromStr for Flow {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let token = TokenStream::from_str(s).map_err(ParseError::LexError)?;
+        check_tokens(token.clone()).map_err(ParseError::SynError)?;
         let flow = parse2(token).map_err(ParseError::SynError)?;
         Ok(flow)
     }
 }
+
+// syn parses nested types recursively, so deeply nested input from the editor would
+// overflow the stack and abort the wasm module instead of failing to parse.
+const MAX_DEPTH: usize = 32;
+
+// syn 1 panics on literals it does not know, such as C strings or the `(/*ERROR*/)`
+// placeholder that proc-macro2 lexes from that text.
+fn supported_literal(literal: &Literal) -> bool {
+    let repr = literal.to_string();
+    let mut bytes = repr.bytes();
+    matches!(
+        (bytes.next(), bytes.next()),
+        (Some(b'"' | b'r' | b'\'' | b'0'..=b'9' | b'-'), _)
+            | (Some(b'b'), Some(b'"' | b'r' | b'\''))
+    )
+}
+
+// Rejects token streams that syn would overflow the stack or panic on: groups and generic
+// arguments nested deeper than `MAX_DEPTH`, and unsupported literals. The `>` of `->`, `>-`
+// and `>-->` does not close generic arguments, nor does `-<` open them.
+pub(crate) fn check_tokens(tokens: TokenStream) -> syn::Result<()> {
+    let mut frames = vec![(tokens.into_iter().peekable(), 0)];
+    let mut depth = 1;
+    let mut after_minus = false;
+    while let Some((tokens, angles)) = frames.last_mut() {
+        let token = match tokens.next() {
+            Some(token) => token,
+            None => {
+                depth -= 1 + *angles;
+                frames.pop();
+                continue;
+            }
+        };
+        let opens = match &token {
+            TokenTree::Literal(literal) if !supported_literal(literal) => {
+                return Err(syn::Error::new(literal.span(), "unsupported literal"));
+            }
+            TokenTree::Group(group) => {
+                frames.push((group.stream().into_iter().peekable(), 0));
+                true
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' && !after_minus => {
+                *angles += 1;
+                true
+            }
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_minus => {
+                let arrow = p.spacing() == Spacing::Joint
+                    && matches!(tokens.peek(), Some(TokenTree::Punct(next)) if next.as_char() == '-');
+                if !arrow && *angles > 0 {
+                    *angles -= 1;
+                    depth -= 1;
+                }
+                false
+            }
+            _ => false,
+        };
+        after_minus = matches!(&token, TokenTree::Punct(p) if p.as_char() == '-' && p.spacing() == Spacing::Joint);
+        if opens {
+            depth += 1;
+            if depth > MAX_DEPTH {
+                return Err(syn::Error::new(
+                    token.span(),
+                    format!("nested more than {} levels deep", MAX_DEPTH),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
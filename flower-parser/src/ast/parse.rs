@@ -1,12 +1,15 @@
-use super::{kw, Flow, Intermediate, Item, Overlay, Reference, Resource, State, Transition};
+use super::{
+    kw, Delay, Flow, Intermediate, Item, Layout, LayoutField, LayoutNode, Overlay, Reference,
+    Resource, State, Transition,
+};
 use proc_macro2::Span;
 use std::fmt;
 use syn::Error;
 use syn::{
-    bracketed,
+    braced, bracketed, parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
-    Result, Token,
+    Lit, Result, Token,
 };
 
 impl Parse for Resource {
@@ -56,12 +59,45 @@ impl Parse for Transition {
         };
         let rarrow_token = input.parse()?;
         let to = input.parse()?;
+        let delay = if input.peek(kw::after) {
+            Some(input.parse()?)
+        } else {
+            None
+        };
         Ok(Transition {
             from,
             gt_sub_token,
             intermediate,
             rarrow_token,
             to,
+            delay,
+        })
+    }
+}
+
+impl Parse for Delay {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let after_token = input.parse()?;
+        let lit: Lit = input.parse()?;
+        let (value, suffix) = match &lit {
+            Lit::Int(i) => (i.base10_parse::<f64>()?, i.suffix()),
+            Lit::Float(f) => (f.base10_parse::<f64>()?, f.suffix()),
+            _ => (0.0, ""),
+        };
+        let millis = match suffix {
+            "s" => value * 1000.0,
+            "ms" => value,
+            _ => {
+                return Err(Error::new(
+                    lit.span(),
+                    "expected a duration such as `3s` or `500ms`",
+                ))
+            }
+        };
+        Ok(Delay {
+            after_token,
+            lit,
+            millis: millis.round() as u64,
         })
     }
 }
@@ -79,6 +115,86 @@ impl Parse for Overlay {
     }
 }
 
+impl Parse for LayoutField {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        let colon_token = input.parse()?;
+        let value = input.parse()?;
+        Ok(LayoutField {
+            name,
+            colon_token,
+            value,
+        })
+    }
+}
+
+impl Parse for LayoutNode {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let element = input.parse()?;
+        let content;
+        let brace = braced!(content in input);
+        let fields: Punctuated<LayoutField, Token![,]> =
+            Punctuated::parse_terminated_with(&content, LayoutField::parse)?;
+
+        let (mut x, mut y, mut colour, mut collapsed) = (None, None, None, None);
+        for field in &fields {
+            let name = field.name.to_string();
+            let repeated = match (name.as_str(), &field.value) {
+                ("x", Lit::Int(i)) => x.replace(i.base10_parse()?).is_some(),
+                ("y", Lit::Int(i)) => y.replace(i.base10_parse()?).is_some(),
+                ("colour", Lit::Str(s)) => colour.replace(s.value()).is_some(),
+                ("collapsed", Lit::Bool(b)) => collapsed.replace(b.value).is_some(),
+                ("x" | "y", value) => return Err(Error::new(value.span(), "expected an integer")),
+                ("colour", value) => return Err(Error::new(value.span(), "expected a string")),
+                ("collapsed", value) => {
+                    return Err(Error::new(value.span(), "expected `true` or `false`"))
+                }
+                _ => {
+                    return Err(Error::new(
+                        field.name.span(),
+                        "expected `x`, `y`, `colour` or `collapsed`",
+                    ))
+                }
+            };
+            if repeated {
+                return Err(Error::new(
+                    field.name.span(),
+                    format!("`{}` is given more than once", name),
+                ));
+            }
+        }
+        let missing = |name| Error::new(brace.span, format!("`{}` is required", name));
+        Ok(LayoutNode {
+            element,
+            brace,
+            x: x.ok_or_else(|| missing("x"))?,
+            y: y.ok_or_else(|| missing("y"))?,
+            fields,
+            colour,
+            collapsed: collapsed.unwrap_or(false),
+        })
+    }
+}
+
+impl Parse for Layout {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pound_token = input.parse()?;
+        let attribute;
+        let bracket = bracketed!(attribute in input);
+        let keyword = attribute.parse()?;
+        let content;
+        let paren = parenthesized!(content in attribute);
+        let punct = Punctuated::parse_terminated_with(&content, LayoutNode::parse)?;
+        Ok(Layout {
+            pound_token,
+            bracket,
+            keyword,
+            paren,
+            punct,
+        })
+    }
+}
+
 impl<K, T, P> Parse for Item<K, T, P>
 where
     K: Parse,
@@ -105,6 +221,11 @@ where
 
 impl Parse for Flow {
     fn parse(input: ParseStream) -> Result<Self> {
+        let layout = if input.peek(Token![#]) {
+            Some(input.parse()?)
+        } else {
+            None
+        };
         let mut resources = None;
         let mut states = None;
         let mut intermediates = None;
@@ -148,6 +269,7 @@ impl Parse for Flow {
         let intermediates = intermediates.ok_or_else(|| item_not_given_error(kw::intermediate))?;
 
         Ok(Flow {
+            layout,
             resources,
             states,
             references,
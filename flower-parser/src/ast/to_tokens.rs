@@ -44,13 +44,21 @@ impl ToTokens for Transition {
         let intermediate = &self.intermediate;
         let rarrow_token = &self.rarrow_token;
         let to = &self.to;
+        let delay = &self.delay;
 
         tokens.extend(quote! {
-            #from #gt_sub_token #intermediate #rarrow_token #to
+            #from #gt_sub_token #intermediate #rarrow_token #to #delay
         })
     }
 }
 
+impl ToTokens for Delay {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.after_token.to_tokens(tokens);
+        self.lit.to_tokens(tokens);
+    }
+}
+
 impl ToTokens for Overlay {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let back = &self.back;
@@ -62,6 +70,33 @@ impl ToTokens for Overlay {
     }
 }
 
+impl ToTokens for LayoutField {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.name.to_tokens(tokens);
+        self.colon_token.to_tokens(tokens);
+        self.value.to_tokens(tokens);
+    }
+}
+
+impl ToTokens for LayoutNode {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.element.to_tokens(tokens);
+        self.brace
+            .surround(tokens, |tokens| self.fields.to_tokens(tokens));
+    }
+}
+
+impl ToTokens for Layout {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.pound_token.to_tokens(tokens);
+        self.bracket.surround(tokens, |tokens| {
+            self.keyword.to_tokens(tokens);
+            self.paren
+                .surround(tokens, |tokens| self.punct.to_tokens(tokens));
+        });
+    }
+}
+
 impl<K, T, P> ToTokens for Item<K, T, P>
 where
     K: ToTokens,
@@ -81,6 +116,7 @@ where
 
 impl ToTokens for Flow {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.layout.to_tokens(tokens);
         self.resources.to_tokens(tokens);
         self.states.to_tokens(tokens);
         self.intermediates.to_tokens(tokens);
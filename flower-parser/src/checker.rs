@@ -0,0 +1,281 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::graph::FlowGraph;
+use super::interpreter::Step;
+use super::raw_ast::RawFlow;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    // Some configuration has `state` on top of the stack.
+    Reachable(String),
+    // From every configuration, one with `state` on top can still be reached.
+    AlwaysReachable(String),
+    AtMostOnce(String),
+    // No active state other than `while_active` borrows `resource` mutably while `while_active` is active.
+    ExclusiveBorrow {
+        resource: String,
+        while_active: String,
+    },
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Property::Reachable(state) => write!(f, "`{}` is reachable", state),
+            Property::AlwaysReachable(state) => write!(f, "`{}` is always reachable", state),
+            Property::AtMostOnce(state) => write!(f, "`{}` is never on the stack twice", state),
+            Property::ExclusiveBorrow {
+                resource,
+                while_active,
+            } => write!(
+                f,
+                "`{}` is never borrowed mutably while `{}` is active",
+                resource, while_active
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub property: Property,
+    pub steps: Vec<Step>,
+    pub stack: Vec<String>,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "violated: {}", self.property)?;
+        for step in &self.steps {
+            writeln!(f, "  {}", step)?;
+        }
+        write!(f, "  stack: [{}]", self.stack.join(", "))
+    }
+}
+
+struct Config {
+    stack: Vec<String>,
+    parent: Option<(usize, Step)>,
+    successors: Vec<usize>,
+    // Configurations at the depth bound have no explored successors.
+    expanded: bool,
+}
+
+// Breadth-first exploration of the stack configurations reachable within `depth` steps.
+pub struct Checker<'a> {
+    graph: FlowGraph<'a>,
+    initial: String,
+    depth: usize,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(flow: &'a RawFlow, initial: &str, depth: usize) -> Self {
+        Checker {
+            graph: FlowGraph::new(flow),
+            initial: initial.to_string(),
+            depth,
+        }
+    }
+
+    pub fn check(&self, property: &Property) -> Result<(), Counterexample> {
+        let configs = self.explore(vec![self.initial.clone()]);
+        let counterexample = |index| Counterexample {
+            property: property.clone(),
+            steps: path(&configs, index),
+            stack: configs[index].stack.clone(),
+        };
+        let violation = match property {
+            Property::Reachable(state) => {
+                if configs.iter().any(|c| c.stack.last() == Some(state)) {
+                    None
+                } else {
+                    Some(0)
+                }
+            }
+            Property::AlwaysReachable(state) => {
+                let reaches = reaching(&configs, state);
+                // The empty stack is where the flow has ended, so nothing is expected of it.
+                (0..configs.len()).find(|&i| !reaches[i] && !configs[i].stack.is_empty())
+            }
+            Property::AtMostOnce(state) => configs
+                .iter()
+                .position(|c| c.stack.iter().filter(|s| *s == state).count() > 1),
+            Property::ExclusiveBorrow {
+                resource,
+                while_active,
+            } => configs.iter().position(|c| {
+                let borrows = self.graph.borrows(&c.stack);
+                let running = &c.stack[self.graph.running_from(&c.stack)..];
+                running.contains(while_active)
+                    && borrows.iter().any(|b| {
+                        b.active && b.mutable && &b.resource == resource && &b.state != while_active
+                    })
+            }),
+        };
+        match violation {
+            Some(index) => Err(counterexample(index)),
+            None => Ok(()),
+        }
+    }
+
+    fn explore(&self, stack: Vec<String>) -> Vec<Config> {
+        let mut configs = vec![Config {
+            stack: stack.clone(),
+            parent: None,
+            successors: Vec::new(),
+            expanded: false,
+        }];
+        let mut seen = HashMap::from([(stack, 0)]);
+        let mut queue = VecDeque::from([(0, 0)]);
+        while let Some((index, depth)) = queue.pop_front() {
+            if depth == self.depth {
+                continue;
+            }
+            configs[index].expanded = true;
+            for step in self.graph.enabled(&configs[index].stack) {
+                let mut stack = configs[index].stack.clone();
+                step.apply_to(&mut stack);
+                if let Some(&next) = seen.get(&stack) {
+                    configs[index].successors.push(next);
+                    continue;
+                }
+                let next = configs.len();
+                seen.insert(stack.clone(), next);
+                queue.push_back((next, depth + 1));
+                configs[index].successors.push(next);
+                configs.push(Config {
+                    stack,
+                    parent: Some((index, step)),
+                    successors: Vec::new(),
+                    expanded: false,
+                });
+            }
+        }
+        configs
+    }
+}
+
+// Which configurations can reach one with `state` on top, walking the explored graph
+// backwards once. Configurations at the depth bound are given the benefit of the doubt, so
+// a counterexample is never an artefact of the bound.
+fn reaching(configs: &[Config], state: &str) -> Vec<bool> {
+    let mut predecessors = vec![Vec::new(); configs.len()];
+    for (index, config) in configs.iter().enumerate() {
+        for &next in &config.successors {
+            predecessors[next].push(index);
+        }
+    }
+    let mut reaches: Vec<bool> = configs
+        .iter()
+        .map(|c| !c.expanded || c.stack.last().is_some_and(|s| s == state))
+        .collect();
+    let mut queue: VecDeque<usize> = (0..configs.len()).filter(|&i| reaches[i]).collect();
+    while let Some(index) = queue.pop_front() {
+        for &previous in &predecessors[index] {
+            if !reaches[previous] {
+                reaches[previous] = true;
+                queue.push_back(previous);
+            }
+        }
+    }
+    reaches
+}
+
+fn path(configs: &[Config], mut index: usize) -> Vec<Step> {
+    let mut steps = Vec::new();
+    while let Some((parent, step)) = &configs[index].parent {
+        steps.push(step.clone());
+        index = *parent;
+    }
+    steps.reverse();
+    steps
+}
+
+#[cfg(test)]
+mod checker_test {
+    use std::str::FromStr;
+
+    use super::{Checker, Property};
+    use crate::{Flow, RawFlow};
+
+    const FLOW_STR: &str = r#"resource: [Save]
+state: [Title, Game, Pause, Autosave, Hud, GameOver, Credits]
+intermediate: []
+reference: [Game -< mut Save, Autosave -< Save, Pause -< mut Save]
+transition: [Title >--> Game, Game >--> GameOver, GameOver >--> Title, Title >--> Credits]
+overlay: [Game ^ Pause, Pause ^ Pause, Game ^ Autosave, Game ^ Hud]
+"#;
+
+    fn raw_flow() -> RawFlow {
+        Flow::from_str(FLOW_STR).unwrap().into()
+    }
+
+    #[test]
+    fn reachability() {
+        let flow = raw_flow();
+        let checker = Checker::new(&flow, "Title", 6);
+        assert!(checker
+            .check(&Property::Reachable("GameOver".to_string()))
+            .is_ok());
+
+        let counterexample = checker
+            .check(&Property::AlwaysReachable("GameOver".to_string()))
+            .unwrap_err();
+        assert_eq!(
+            counterexample.to_string(),
+            "violated: `GameOver` is always reachable\n  Title >--> Credits\n  stack: [Credits]"
+        );
+    }
+
+    #[test]
+    fn always_reachable_holds_in_a_loop() {
+        let flow: RawFlow = Flow::from_str(
+            "resource: [] state: [A, B] intermediate: [] reference: [] \
+             transition: [A >--> B, B >--> A] overlay: [A ^ B]",
+        )
+        .unwrap()
+        .into();
+        let checker = Checker::new(&flow, "A", 6);
+        assert!(checker
+            .check(&Property::AlwaysReachable("A".to_string()))
+            .is_ok());
+        assert!(checker
+            .check(&Property::AlwaysReachable("B".to_string()))
+            .is_ok());
+
+        let flow: RawFlow = Flow::from_str(
+            "resource: [] state: [A] intermediate: [] reference: [] \
+             transition: [A >--> A] overlay: []",
+        )
+        .unwrap()
+        .into();
+        assert!(Checker::new(&flow, "A", 6)
+            .check(&Property::AlwaysReachable("A".to_string()))
+            .is_ok());
+    }
+
+    #[test]
+    fn stack_invariants() {
+        let flow = raw_flow();
+        let checker = Checker::new(&flow, "Title", 6);
+        let counterexample = checker
+            .check(&Property::AtMostOnce("Pause".to_string()))
+            .unwrap_err();
+        assert_eq!(counterexample.stack, ["Game", "Pause", "Pause"]);
+
+        assert!(checker
+            .check(&Property::ExclusiveBorrow {
+                resource: "Save".to_string(),
+                while_active: "Autosave".to_string(),
+            })
+            .is_ok());
+        let counterexample = checker
+            .check(&Property::ExclusiveBorrow {
+                resource: "Save".to_string(),
+                while_active: "Hud".to_string(),
+            })
+            .unwrap_err();
+        assert_eq!(counterexample.stack, ["Game", "Hud"]);
+    }
+}
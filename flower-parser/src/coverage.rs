@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::raw_ast::RawFlow;
+
+pub fn transition_key(from: &str, intermediate: Option<&str>, to: &str) -> String {
+    match intermediate {
+        Some(intermediate) => format!("{} >- {} -> {}", from, intermediate, to),
+        None => format!("{} >--> {}", from, to),
+    }
+}
+
+pub fn overlay_key(back: &str, front: &str) -> String {
+    format!("{} ^ {}", back, front)
+}
+
+// Each process writes its counts to `<report>.<pid>`, so test binaries running at the same
+// time never overwrite each other's counts.
+pub fn process_report(report: &Path, pid: u32) -> PathBuf {
+    let mut path = report.as_os_str().to_owned();
+    path.push(format!(".{}", pid));
+    PathBuf::from(path)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowCoverage {
+    pub states: BTreeMap<String, u64>,
+    pub transitions: BTreeMap<String, u64>,
+    pub overlays: BTreeMap<String, u64>,
+}
+
+impl FlowCoverage {
+    pub fn merge(&mut self, other: &FlowCoverage) {
+        for (mine, theirs) in [
+            (&mut self.states, &other.states),
+            (&mut self.transitions, &other.transitions),
+            (&mut self.overlays, &other.overlays),
+        ] {
+            for (key, count) in theirs {
+                *mine.entry(key.clone()).or_default() += count;
+            }
+        }
+    }
+}
+
+// Counts keyed by the module path of the `flow!` invocation that produced them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub flows: BTreeMap<String, FlowCoverage>,
+}
+
+impl CoverageReport {
+    pub fn merge(&mut self, other: &CoverageReport) {
+        for (flow, coverage) in &other.flows {
+            self.flows.entry(flow.clone()).or_default().merge(coverage);
+        }
+    }
+
+    // Reads `report` merged with every per-process report written beside it. It is enough
+    // for either to exist.
+    pub fn read(report: &Path) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        if report.exists() {
+            paths.push(report.to_path_buf());
+        }
+        let name = report.file_name().unwrap_or_default().to_string_lossy();
+        let dir = match report.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let file = path.file_name().unwrap_or_default().to_string_lossy();
+                let pid = file.strip_prefix(&*name).and_then(|s| s.strip_prefix('.'));
+                if pid.is_some_and(|pid| !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit()))
+                {
+                    paths.push(path);
+                }
+            }
+        }
+        if paths.is_empty() {
+            let message = format!("{}: no coverage report", report.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, message));
+        }
+        let mut merged = CoverageReport::default();
+        for path in paths {
+            let part: CoverageReport =
+                serde_json::from_str(&fs::read_to_string(&path)?).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: {}", path.display(), e),
+                    )
+                })?;
+            merged.merge(&part);
+        }
+        Ok(merged)
+    }
+
+    // Sums the given flows, or every flow in the report when `flow` is `None`.
+    pub fn select(&self, flow: Option<&str>) -> FlowCoverage {
+        let mut total = FlowCoverage::default();
+        for (name, coverage) in &self.flows {
+            if flow.is_none_or(|flow| flow == name) {
+                total.merge(coverage);
+            }
+        }
+        total
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Covered {
+    pub name: String,
+    pub count: u64,
+}
+
+// Every element of the static flow with the number of times the runtime took it;
+// a count of zero marks an untested element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageMap {
+    pub states: Vec<Covered>,
+    pub transitions: Vec<Covered>,
+    pub overlays: Vec<Covered>,
+}
+
+impl CoverageMap {
+    pub fn new(flow: &RawFlow, coverage: &FlowCoverage) -> Self {
+        let covered = |counts: &BTreeMap<String, u64>, name: String| Covered {
+            count: counts.get(&name).copied().unwrap_or_default(),
+            name,
+        };
+        CoverageMap {
+            states: flow
+                .states
+                .iter()
+                .map(|s| covered(&coverage.states, s.0.clone()))
+                .collect(),
+            transitions: flow
+                .transitions
+                .iter()
+                .map(|t| {
+                    let intermediate = t.intermediate.as_ref().map(|i| i.0.as_str());
+                    let key = transition_key(&t.from.0, intermediate, &t.to.0);
+                    covered(&coverage.transitions, key)
+                })
+                .collect(),
+            overlays: flow
+                .overlays
+                .iter()
+                .map(|o| covered(&coverage.overlays, overlay_key(&o.back.0, &o.front.0)))
+                .collect(),
+        }
+    }
+
+    pub fn untested(&self) -> impl Iterator<Item = &Covered> {
+        self.states
+            .iter()
+            .chain(&self.transitions)
+            .chain(&self.overlays)
+            .filter(|c| c.count == 0)
+    }
+
+    pub fn to_dot(&self, flow: &RawFlow) -> String {
+        let color = |count| if count == 0 { "red" } else { "darkgreen" };
+        let mut dot = String::from("digraph flow {\n");
+        for (state, c) in flow.states.iter().zip(&self.states) {
+            writeln!(
+                dot,
+                "    {:?} [color={}, label=\"{} ({})\"];",
+                state.0,
+                color(c.count),
+                state.0.escape_debug(),
+                c.count
+            )
+            .unwrap();
+        }
+        for (t, c) in flow.transitions.iter().zip(&self.transitions) {
+            let label = t.intermediate.as_ref().map_or("", |i| i.0.as_str());
+            writeln!(
+                dot,
+                "    {:?} -> {:?} [color={}, label=\"{} ({})\"];",
+                t.from.0,
+                t.to.0,
+                color(c.count),
+                label.escape_debug(),
+                c.count
+            )
+            .unwrap();
+        }
+        for (o, c) in flow.overlays.iter().zip(&self.overlays) {
+            writeln!(
+                dot,
+                "    {:?} -> {:?} [style=dashed, color={}, label=\"^ ({})\"];",
+                o.back.0,
+                o.front.0,
+                color(c.count),
+                c.count
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>flow coverage</title>\n\
+             <style>.untested { background: #f8d0d0; } .tested { background: #d0f0d0; }</style>\n\
+             </head>\n<body>\n",
+        );
+        for (title, covered) in [
+            ("States", &self.states),
+            ("Transitions", &self.transitions),
+            ("Overlays", &self.overlays),
+        ] {
+            writeln!(html, "<h2>{}</h2>\n<table>", title).unwrap();
+            for c in covered {
+                let class = if c.count == 0 { "untested" } else { "tested" };
+                writeln!(
+                    html,
+                    "<tr class=\"{}\"><td>{}</td><td>{}</td></tr>",
+                    class,
+                    escape_html(&c.name),
+                    c.count
+                )
+                .unwrap();
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod coverage_test {
+    use std::str::FromStr;
+
+    use std::fs;
+
+    use super::{process_report, CoverageMap, CoverageReport, FlowCoverage};
+    use crate::{Flow, RawFlow};
+
+    const FLOW_STR: &str = r#"resource: []
+state: [Title, Game, Pause]
+intermediate: [Start]
+reference: []
+transition: [Title >- Start -> Game, Game >--> Title]
+overlay: [Game ^ Pause]
+"#;
+
+    #[test]
+    fn merge_and_map() {
+        let run = |transition: &str| {
+            let mut coverage = FlowCoverage::default();
+            coverage.states.insert("Title".to_string(), 1);
+            coverage.transitions.insert(transition.to_string(), 1);
+            let mut report = CoverageReport::default();
+            report.flows.insert("game".to_string(), coverage);
+            report
+        };
+        let mut report = run("Title >- Start -> Game");
+        report.merge(&run("Title >- Start -> Game"));
+        assert_eq!(
+            report.flows["game"].transitions["Title >- Start -> Game"],
+            2
+        );
+        assert_eq!(report.flows["game"].states["Title"], 2);
+
+        let flow: RawFlow = Flow::from_str(FLOW_STR).unwrap().into();
+        let map = CoverageMap::new(&flow, &report.select(None));
+        let untested: Vec<_> = map.untested().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            untested,
+            ["Game", "Pause", "Game >--> Title", "Game ^ Pause"]
+        );
+        assert!(map
+            .to_dot(&flow)
+            .contains("\"Game\" -> \"Title\" [color=red, label=\" (0)\"];"));
+        assert!(map
+            .to_html()
+            .contains("<tr class=\"untested\"><td>Game &gt;--&gt; Title</td><td>0</td></tr>"));
+    }
+
+    #[test]
+    fn process_reports_are_read_together() {
+        let dir = std::env::temp_dir().join(format!("flower-coverage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let report = dir.join("coverage.json");
+        let part = |pid, count| {
+            let json = format!(
+                r#"{{"flows":{{"game":{{"states":{{"Title":{}}},"transitions":{{}},"overlays":{{}}}}}}}}"#,
+                count
+            );
+            fs::write(process_report(&report, pid), json).unwrap();
+        };
+        assert!(CoverageReport::read(&report).is_err());
+        part(1, 2);
+        part(20, 3);
+        fs::write(dir.join("coverage.json.bak"), "not json").unwrap();
+        fs::write(dir.join("coverage.jsonl.3"), "not json").unwrap();
+        let merged = CoverageReport::read(&report);
+        fs::write(
+            &report,
+            r#"{"flows":{"menu":{"states":{},"transitions":{},"overlays":{}}}}"#,
+        )
+        .unwrap();
+        let with_report = CoverageReport::read(&report);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(merged.unwrap().flows["game"].states["Title"], 5);
+        let with_report = with_report.unwrap();
+        assert_eq!(with_report.flows["game"].states["Title"], 5);
+        assert!(with_report.flows.contains_key("menu"));
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use proc_macro2::{Group, LineColumn, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use syn::parse2;
+use syn::spanned::Spanned;
+
+use super::ast::{check_tokens, Flow, ParseError};
+use super::raw_ast::{self, RawFlow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// `line` is 1-based and `column` is a 0-based count of characters, as in proc-macro2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<LineColumn> for Position {
+    fn from(lc: LineColumn) -> Self {
+        Position {
+            line: lc.line,
+            column: lc.column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub start: Option<Position>,
+    pub end: Option<Position>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: String, span: Option<Span>) -> Self {
+        // Spans without a location, such as `Span::call_site()`, report line 0.
+        let span = span.filter(|span| span.start().line > 0);
+        Diagnostic {
+            severity,
+            message,
+            start: span.map(|span| span.start().into()),
+            end: span.map(|span| span.end().into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Outcome<T> {
+    pub ok: bool,
+    pub value: Option<T>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<T> Outcome<T> {
+    fn new(value: Option<T>, diagnostics: Vec<Diagnostic>) -> Self {
+        Outcome {
+            ok: value.is_some(),
+            value,
+            diagnostics,
+        }
+    }
+
+    pub fn ok(value: T) -> Self {
+        Outcome::new(Some(value), Vec::new())
+    }
+
+    // A failure with a single error that has no position.
+    pub fn error(message: String) -> Self {
+        Outcome::new(None, vec![Diagnostic::new(Severity::Error, message, None)])
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Outcome<U> {
+        Outcome::new(self.value.map(f), self.diagnostics)
+    }
+}
+
+fn parse_errors(e: ParseError) -> Vec<Diagnostic> {
+    match e {
+        ParseError::LexError(e) => vec![Diagnostic::new(
+            Severity::Error,
+            e.to_string(),
+            Some(e.span()),
+        )],
+        ParseError::SynError(e) => e
+            .into_iter()
+            .map(|e| Diagnostic::new(Severity::Error, e.to_string(), Some(e.span())))
+            .collect(),
+    }
+}
+
+// Names that are used or laid out without being declared, or declared or laid out more than once.
+fn warnings(flow: &Flow) -> Vec<Diagnostic> {
+    fn name<T: ToTokens>(t: &T) -> String {
+        t.to_token_stream().to_string()
+    }
+    let mut warnings = Vec::new();
+    let mut warn = |message: String, span: Span| {
+        warnings.push(Diagnostic::new(Severity::Warning, message, Some(span)))
+    };
+
+    let mut declare = |kind: &str, items: Vec<(String, Span)>| {
+        let mut declared = HashSet::new();
+        for (name, span) in items {
+            if !declared.insert(name.clone()) {
+                warn(
+                    format!("{} `{}` is declared more than once", kind, name),
+                    span,
+                );
+            }
+        }
+        declared
+    };
+    let resources = declare(
+        "resource",
+        flow.resources
+            .punct
+            .iter()
+            .map(|r| (name(r), r.0.span()))
+            .collect(),
+    );
+    let states = declare(
+        "state",
+        flow.states
+            .punct
+            .iter()
+            .map(|s| (name(s), s.0.span()))
+            .collect(),
+    );
+    let intermediates = declare(
+        "intermediate",
+        flow.intermediates
+            .punct
+            .iter()
+            .map(|i| (name(i), i.0.span()))
+            .collect(),
+    );
+
+    let mut used = Vec::new();
+    for r in &flow.references.punct {
+        used.push(("state", &states, name(&r.state), r.state.0.span()));
+        used.push((
+            "resource",
+            &resources,
+            name(&r.resource),
+            r.resource.0.span(),
+        ));
+    }
+    for t in &flow.transitions.punct {
+        used.push(("state", &states, name(&t.from), t.from.0.span()));
+        if let Some(i) = &t.intermediate {
+            used.push(("intermediate", &intermediates, name(i), i.0.span()));
+        }
+        used.push(("state", &states, name(&t.to), t.to.0.span()));
+    }
+    for o in &flow.overlays.punct {
+        used.push(("state", &states, name(&o.back), o.back.0.span()));
+        used.push(("state", &states, name(&o.front), o.front.0.span()));
+    }
+    for (kind, declared, name, span) in used {
+        if !declared.contains(&name) {
+            warn(format!("{} `{}` is not declared", kind, name), span);
+        }
+    }
+    let mut laid_out = HashSet::new();
+    for node in flow.layout.iter().flat_map(|layout| &layout.punct) {
+        let element = name(&node.element);
+        // The `RawFlow` keeps only the last layout of each name.
+        if !laid_out.insert(element.clone()) {
+            warn(
+                format!("`{}` has more than one layout; the last is kept", element),
+                node.element.span(),
+            );
+        }
+        let declared = [&resources, &states, &intermediates]
+            .iter()
+            .any(|declared| declared.contains(&element));
+        if !declared {
+            warn(
+                format!("`{}` has a layout but is not declared", element),
+                node.element.span(),
+            );
+        }
+    }
+    warnings
+}
+
+// Parses flow text into a `RawFlow`, reporting syntax errors and undeclared names with their positions.
+pub fn parse_flow(text: &str) -> Outcome<RawFlow> {
+    let outcome = match Flow::from_str(text) {
+        Ok(flow) => {
+            let warnings = warnings(&flow);
+            Outcome::new(Some(flow.into()), warnings)
+        }
+        Err(e) => Outcome::new(None, parse_errors(e)),
+    };
+    // The spans are all `Position`s by now; without this, proc-macro2 keeps the source of every
+    // parse for the life of the thread, which is the whole session in the editor and the LSP.
+    proc_macro2::extra::invalidate_current_thread_spans();
+    outcome
+}
+
+// Prints a `RawFlow` as flow text; the positions of errors are unknown since there is no text yet.
+pub fn print_flow(flow: RawFlow) -> Outcome<String> {
+    match Flow::try_from(flow) {
+        Ok(flow) => Outcome::ok(RawFlow::from(flow).to_string()),
+        Err(e) => {
+            let message = match e {
+                raw_ast::Error::SynError(e) => e.to_string(),
+                raw_ast::Error::LexError(e) => e.to_string(),
+            };
+            Outcome::error(message)
+        }
+    }
+}
+
+// Walks `text` from `at` past each of `tokens` and the whitespace and comments before it,
+// recording where each token starts and ends. Fails on text the tokens do not spell out, such
+// as doc comments.
+fn token_positions(
+    text: &[char],
+    at: &mut usize,
+    tokens: TokenStream,
+    positions: &mut Vec<(usize, usize)>,
+) -> Option<()> {
+    for token in tokens {
+        skip_trivia(text, at);
+        let index = positions.len();
+        positions.push((*at, *at));
+        let spelled = match &token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    proc_macro2::Delimiter::Parenthesis => ('(', ')'),
+                    proc_macro2::Delimiter::Brace => ('{', '}'),
+                    proc_macro2::Delimiter::Bracket => ('[', ']'),
+                    proc_macro2::Delimiter::None => return None,
+                };
+                (text.get(*at) == Some(&open)).then_some(())?;
+                *at += 1;
+                token_positions(text, at, group.stream(), positions)?;
+                skip_trivia(text, at);
+                (text.get(*at) == Some(&close)).then_some(())?;
+                *at += 1;
+                positions[index].1 = *at;
+                continue;
+            }
+            token => token.to_string(),
+        };
+        let spelled: Vec<char> = spelled.chars().collect();
+        (text.get(*at..*at + spelled.len())? == spelled.as_slice()).then_some(())?;
+        *at += spelled.len();
+        positions[index].1 = *at;
+    }
+    Some(())
+}
+
+fn skip_trivia(text: &[char], at: &mut usize) {
+    loop {
+        match text.get(*at..) {
+            Some([c, ..]) if c.is_whitespace() => *at += 1,
+            Some(['/', '/', ..]) => {
+                while text.get(*at).is_some_and(|&c| c != '\n') {
+                    *at += 1;
+                }
+            }
+            Some(['/', '*', ..]) => {
+                let mut depth = 0;
+                while *at < text.len() {
+                    match &text[*at..] {
+                        ['/', '*', ..] => (depth, *at) = (depth + 1, *at + 2),
+                        ['*', '/', ..] => (depth, *at) = (depth - 1, *at + 2),
+                        _ => *at += 1,
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => return,
+        }
+    }
+}
+
+// Where lexing fails on delimiters, the way proc-macro2 reports it: at a closing delimiter that
+// matches nothing, or at the innermost group left open.
+fn unbalanced(text: &[char]) -> Option<usize> {
+    let mut open = Vec::new();
+    let mut at = 0;
+    loop {
+        skip_trivia(text, &mut at);
+        let Some(c) = text.get(at) else {
+            return open.last().map(|&(_, at)| at);
+        };
+        match c {
+            c @ ('(' | '[' | '{') => open.push((*c, at)),
+            c @ (')' | ']' | '}') => match open.pop() {
+                Some((o, _)) if matches!((o, c), ('(', ')') | ('[', ']') | ('{', '}')) => {}
+                _ => return Some(at),
+            },
+            '"' => {
+                let start = at;
+                at += 1;
+                while text.get(at) != Some(&'"') {
+                    match text.get(at) {
+                        Some('\\') => at += 2,
+                        Some(_) => at += 1,
+                        None => return Some(start),
+                    }
+                }
+            }
+            _ => {}
+        }
+        at += 1;
+    }
+}
+
+// Keeps the first `remaining` tokens of `tokens`, counting a group before its contents, and
+// puts `rest` in place of the others.
+fn truncate(
+    tokens: TokenStream,
+    remaining: &mut usize,
+    rest: &mut Option<TokenStream>,
+) -> TokenStream {
+    let mut kept = TokenStream::new();
+    for token in tokens {
+        if *remaining == 0 {
+            kept.extend(rest.take());
+            break;
+        }
+        *remaining -= 1;
+        let token = match token {
+            TokenTree::Group(group) => {
+                let inner = truncate(group.stream(), remaining, rest);
+                TokenTree::Group(Group::new(group.delimiter(), inner))
+            }
+            token => token,
+        };
+        kept.extend([token]);
+    }
+    kept
+}
+
+// Where parsing `text` fails, without relying on spans: inside a proc macro every span parsed
+// from a string is the call site. The tokens are parsed again cut short after each one in turn.
+// Every cut that keeps the culprit fails the same way as the whole text, while earlier cuts fail
+// at the end of their input, so the culprit is the last token of the earliest cut in that final
+// run. Errors at the end of the whole input have no position, nor do lexing errors other than
+// unbalanced delimiters.
+pub fn error_position(text: &str) -> Option<Position> {
+    let chars: Vec<char> = text.chars().collect();
+    let position = |at: usize| {
+        let before = &chars[..at];
+        let line_start = before.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.iter().filter(|&&c| c == '\n').count() + 1,
+            column: before.len() - line_start,
+        }
+    };
+    let Ok(tokens) = TokenStream::from_str(text) else {
+        return unbalanced(&chars).map(position);
+    };
+    let cut = |count, rest| truncate(tokens.clone(), &mut { count }, &mut { rest });
+    let mut positions = Vec::new();
+    token_positions(&chars, &mut 0, tokens.clone(), &mut positions)?;
+    // The depth and literal checks run before syn and fail on the culprit itself.
+    let parse = |tokens: TokenStream| {
+        check_tokens(tokens.clone())?;
+        parse2::<Flow>(tokens)
+    };
+    let message = parse(tokens.clone()).err()?.to_string();
+    let fails = |count, rest| parse(cut(count, rest)).is_err_and(|e| e.to_string() == message);
+    let count = (1..=positions.len())
+        .rev()
+        .take_while(|&count| fails(count, None))
+        .last()?;
+    // syn reports a missing punctuation at the end of the input like any other, so a cut just
+    // before the culprit can fail the same way. Supplying the punctuation tells them apart.
+    let expected = message
+        .strip_prefix("expected `")
+        .and_then(|m| m.strip_suffix('`'))
+        .filter(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_punctuation()))
+        .and_then(|p| TokenStream::from_str(p).ok());
+    let at = match expected {
+        Some(punct) if count < positions.len() && !fails(count, Some(punct.clone())) => {
+            positions[count].0
+        }
+        // The group around the last token ran out; syn points at its closing delimiter.
+        _ if message.starts_with("unexpected end of input") => {
+            let mut at = positions[count - 1].1;
+            skip_trivia(&chars, &mut at);
+            chars
+                .get(at)
+                .filter(|c| matches!(c, ')' | ']' | '}'))
+                .map(|_| at)?
+        }
+        _ => positions[count - 1].0,
+    };
+    Some(position(at))
+}
+
+#[cfg(test)]
+mod diagnostic_test {
+    use super::{error_position, parse_flow, print_flow, Diagnostic, Position, Severity};
+
+    fn at(line: usize, column: usize) -> Option<Position> {
+        Some(Position { line, column })
+    }
+
+    #[test]
+    fn syntax_error_has_position() {
+        let outcome = parse_flow("resource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: [Title > Title]\noverlay: []\n");
+        assert!(!outcome.ok);
+        assert_eq!(
+            outcome.diagnostics,
+            [Diagnostic {
+                severity: Severity::Error,
+                message: "expected `>-`".to_string(),
+                start: at(5, 19),
+                end: at(5, 20),
+            }]
+        );
+    }
+
+    #[test]
+    fn error_position_matches_the_span() {
+        let text = "// game\nresource: []\nstate: [Title]\nintermediate: []\nreference: []\n/* timed */ transition: [Title > Title]\noverlay: []\n";
+        assert_eq!(error_position(text), at(6, 31));
+        assert_eq!(parse_flow(text).diagnostics[0].start, at(6, 31));
+        let base = "resource: [Save]\nstate: [Title, Game]\nintermediate: [Start]\nreference: [Game -< Save]\ntransition: [Title >- Start -> Game]\noverlay: [Game ^ Title]\n";
+        let nested = format!("state: [{}Title{}]", "Vec<".repeat(40), ">".repeat(40));
+        for (from, to) in [
+            ("Title >- Start", "Title >-> Start"),
+            ("Title, Game", "Title,, Game"),
+            ("overlay", "overlays"),
+            ("-< Save", "-< mut mut Save"),
+            ("Game ^ Title", "Game ^ ^ Title"),
+            ("state: [Title, Game]", &nested),
+            (
+                "resource: [Save]",
+                "#[layout(Game { x: a, y: 0 })] resource: [Save]",
+            ),
+            ("-> Game]", "-> Game after]"),
+            ("-> Game]", "-> Game after 3x]"),
+            ("-< Save", "-< Save<"),
+            ("-> Game]", "-> Game, ->]"),
+        ] {
+            let text = base.replace(from, to);
+            let outcome = parse_flow(&text);
+            assert!(!outcome.ok);
+            assert_eq!(
+                error_position(&text),
+                outcome.diagnostics[0].start,
+                "{}",
+                text
+            );
+        }
+        for text in [
+            "resource: [] state: [",
+            "resource: [] state: [)",
+            "resource: [] state: [(\"]\"]",
+            "resource: [] state: [\"Title]",
+        ] {
+            assert!(error_position(text).is_some());
+            assert_eq!(error_position(text), parse_flow(text).diagnostics[0].start);
+        }
+    }
+
+    #[test]
+    fn undeclared_names_warn() {
+        let outcome = parse_flow("resource: []\nstate: [Title, Title]\nintermediate: []\nreference: []\ntransition: [Title >--> Game]\noverlay: []\n");
+        assert!(outcome.ok);
+        let warnings: Vec<_> = outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.message.as_str(), d.start, d.end))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (
+                    Severity::Warning,
+                    "state `Title` is declared more than once",
+                    at(2, 15),
+                    at(2, 20)
+                ),
+                (
+                    Severity::Warning,
+                    "state `Game` is not declared",
+                    at(5, 24),
+                    at(5, 28)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn undeclared_layout_warns() {
+        let outcome = parse_flow("#[layout(Title { x: 0, y: 0 }, Game { x: 1, y: 0 })]\nresource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: []\noverlay: []\n");
+        assert!(outcome.ok);
+        assert_eq!(
+            outcome.diagnostics,
+            [Diagnostic {
+                severity: Severity::Warning,
+                message: "`Game` has a layout but is not declared".to_string(),
+                start: at(1, 31),
+                end: at(1, 35),
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_layout_warns() {
+        let outcome = parse_flow("#[layout(Title { x: 0, y: 0 }, Title { x: 1, y: 0 })]\nresource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: []\noverlay: []\n");
+        assert!(outcome.ok);
+        assert_eq!(
+            outcome.diagnostics,
+            [Diagnostic {
+                severity: Severity::Warning,
+                message: "`Title` has more than one layout; the last is kept".to_string(),
+                start: at(1, 31),
+                end: at(1, 36),
+            }]
+        );
+        let layout = outcome.value.unwrap().layout.unwrap();
+        assert_eq!(layout["Title"].x, 1);
+    }
+
+    #[test]
+    fn print_reports_invalid_names() {
+        let mut flow = parse_flow("resource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: []\noverlay: []\n")
+            .value
+            .unwrap();
+        assert_eq!(print_flow(flow.clone()).value.unwrap(), "resource: []\nstate: [Title]\nintermediate: []\nreference: []\ntransition: []\noverlay: []\n");
+        flow.states[0].0 = "Title -".to_string();
+        let outcome = print_flow(flow);
+        assert!(!outcome.ok);
+        assert_eq!(outcome.diagnostics[0].start, None);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::ast::Flow;
+use super::pretty::{overlay, reference, transition, ty};
+use super::raw_ast::{RawFlow, RawOverlay, RawReference, RawState, RawTransition};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Changes<T> {
+    pub removed: Vec<T>,
+    pub added: Vec<T>,
+}
+
+impl<T> Default for Changes<T> {
+    fn default() -> Self {
+        Changes {
+            removed: Vec::new(),
+            added: Vec::new(),
+        }
+    }
+}
+
+impl<T> Changes<T> {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+// What changed between two flows. Elements are matched by name and entries by value, so
+// reordering a section is not a change, and neither is the layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowDiff {
+    pub renamed_states: Vec<Rename>,
+    pub resources: Changes<String>,
+    pub states: Changes<String>,
+    pub intermediates: Changes<String>,
+    pub references: Changes<RawReference>,
+    // References in both flows whose mutability changed, as they are in the new flow.
+    pub mutability: Vec<RawReference>,
+    pub transitions: Changes<RawTransition>,
+    pub overlays: Changes<RawOverlay>,
+}
+
+impl FlowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.renamed_states.is_empty()
+            && self.resources.is_empty()
+            && self.states.is_empty()
+            && self.intermediates.is_empty()
+            && self.references.is_empty()
+            && self.mutability.is_empty()
+            && self.transitions.is_empty()
+            && self.overlays.is_empty()
+    }
+}
+
+// Entries of `old` missing from `new` and entries of `new` missing from `old`, in the order
+// each flow declares them.
+fn changes<T: Clone + PartialEq>(old: &[T], new: &[T]) -> Changes<T> {
+    Changes {
+        removed: old.iter().filter(|e| !new.contains(e)).cloned().collect(),
+        added: new.iter().filter(|e| !old.contains(e)).cloned().collect(),
+    }
+}
+
+// Names as token strings, so that `a::B` from JSON matches `a :: B` from flow text.
+pub(crate) fn canonical(flow: &RawFlow) -> RawFlow {
+    match Flow::try_from(flow.clone()) {
+        Ok(flow) => flow.into(),
+        Err(_) => flow.clone(),
+    }
+}
+
+// Everything `state` is connected to, with `None` standing for the state itself, so that a
+// renamed state has the same neighbourhood under both names.
+type Neighbourhood = (
+    Vec<(String, bool)>,
+    Vec<(Option<String>, Option<String>, Option<String>, Option<u64>)>,
+    Vec<(Option<String>, Option<String>)>,
+);
+
+fn neighbourhood(flow: &RawFlow, state: &str) -> Neighbourhood {
+    let other = |s: &RawState| Some(s.0.clone()).filter(|s| s != state);
+    let mut references: Vec<_> = flow
+        .references
+        .iter()
+        .filter(|r| r.state.0 == state)
+        .map(|r| (r.resource.0.clone(), r.mutable))
+        .collect();
+    let mut transitions: Vec<_> = flow
+        .transitions
+        .iter()
+        .filter(|t| t.from.0 == state || t.to.0 == state)
+        .map(|t| {
+            let intermediate = t.intermediate.as_ref().map(|i| i.0.clone());
+            (other(&t.from), intermediate, other(&t.to), t.after_ms)
+        })
+        .collect();
+    let mut overlays: Vec<_> = flow
+        .overlays
+        .iter()
+        .filter(|o| o.back.0 == state || o.front.0 == state)
+        .map(|o| (other(&o.back), other(&o.front)))
+        .collect();
+    references.sort();
+    transitions.sort();
+    overlays.sort();
+    (references, transitions, overlays)
+}
+
+// Pairs a removed state with an added one when each is the only one of its side with that
+// neighbourhood. A state connected to nothing says nothing about what it was renamed from.
+fn renames(old: &RawFlow, new: &RawFlow, states: &Changes<String>) -> Vec<Rename> {
+    let by_neighbourhood = |flow, names: &[String]| {
+        let mut map: BTreeMap<Neighbourhood, Vec<String>> = BTreeMap::new();
+        for name in names {
+            let neighbourhood = neighbourhood(flow, name);
+            if neighbourhood != Neighbourhood::default() {
+                map.entry(neighbourhood).or_default().push(name.clone());
+            }
+        }
+        map
+    };
+    let removed = by_neighbourhood(old, &states.removed);
+    let added = by_neighbourhood(new, &states.added);
+    let mut renames: Vec<_> = removed
+        .iter()
+        .filter_map(
+            |(neighbourhood, from)| match (&from[..], added.get(neighbourhood)) {
+                ([from], Some(to)) if to.len() == 1 => Some(Rename {
+                    from: from.clone(),
+                    to: to[0].clone(),
+                }),
+                _ => None,
+            },
+        )
+        .collect();
+    let position = |name: &String| states.removed.iter().position(|s| s == name);
+    renames.sort_by_key(|r| position(&r.from));
+    renames
+}
+
+pub fn diff(old: &RawFlow, new: &RawFlow) -> FlowDiff {
+    let (mut old, new) = (canonical(old), canonical(new));
+    let names = |flow: &RawFlow| -> [Vec<String>; 3] {
+        [
+            flow.resources.iter().map(|r| r.0.clone()).collect(),
+            flow.states.iter().map(|s| s.0.clone()).collect(),
+            flow.intermediates.iter().map(|i| i.0.clone()).collect(),
+        ]
+    };
+    let [old_resources, old_states, old_intermediates] = names(&old);
+    let [new_resources, new_states, new_intermediates] = names(&new);
+
+    let mut states = changes(&old_states, &new_states);
+    let renamed_states = renames(&old, &new, &states);
+    states
+        .removed
+        .retain(|s| !renamed_states.iter().any(|r| &r.from == s));
+    states
+        .added
+        .retain(|s| !renamed_states.iter().any(|r| &r.to == s));
+
+    // Compares the other sections as if the old flow already used the new names.
+    let rename = |s: &mut RawState| {
+        if let Some(r) = renamed_states.iter().find(|r| r.from == s.0) {
+            s.0 = r.to.clone();
+        }
+    };
+    old.references.iter_mut().for_each(|r| rename(&mut r.state));
+    for t in &mut old.transitions {
+        rename(&mut t.from);
+        rename(&mut t.to);
+    }
+    for o in &mut old.overlays {
+        rename(&mut o.back);
+        rename(&mut o.front);
+    }
+
+    let same = |a: &RawReference, b: &RawReference| a.state == b.state && a.resource == b.resource;
+    let missing = |from: &[RawReference], to: &[RawReference]| -> Vec<RawReference> {
+        let to_has = |a: &&RawReference| to.iter().any(|b| same(a, b));
+        from.iter().filter(|a| !to_has(a)).cloned().collect()
+    };
+    let references = Changes {
+        removed: missing(&old.references, &new.references),
+        added: missing(&new.references, &old.references),
+    };
+    let mutability = new
+        .references
+        .iter()
+        .filter(|b| {
+            let old = old.references.iter().find(|a| same(a, b));
+            old.is_some_and(|a| a.mutable != b.mutable)
+        })
+        .cloned()
+        .collect();
+
+    FlowDiff {
+        renamed_states,
+        resources: changes(&old_resources, &new_resources),
+        states,
+        intermediates: changes(&old_intermediates, &new_intermediates),
+        references,
+        mutability,
+        transitions: changes(&old.transitions, &new.transitions),
+        overlays: changes(&old.overlays, &new.overlays),
+    }
+}
+
+fn lines<T>(
+    f: &mut fmt::Formatter<'_>,
+    kind: &str,
+    changes: &Changes<T>,
+    show: impl Fn(&T) -> String,
+) -> fmt::Result {
+    for removed in &changes.removed {
+        writeln!(f, "- {} {}", kind, show(removed))?;
+    }
+    for added in &changes.added {
+        writeln!(f, "+ {} {}", kind, show(added))?;
+    }
+    Ok(())
+}
+
+// One line per change: `-` for removed, `+` for added and `~` for renamed or changed.
+impl fmt::Display for FlowDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |s: &String| ty(s);
+        for r in &self.renamed_states {
+            writeln!(f, "~ state {} -> {}", ty(&r.from), ty(&r.to))?;
+        }
+        lines(f, "state", &self.states, name)?;
+        lines(f, "resource", &self.resources, name)?;
+        lines(f, "intermediate", &self.intermediates, name)?;
+        lines(f, "reference", &self.references, reference)?;
+        for r in &self.mutability {
+            let now = if r.mutable { "mutable" } else { "immutable" };
+            let (state, resource) = (ty(&r.state.0), ty(&r.resource.0));
+            writeln!(f, "~ reference {} -< {} is now {}", state, resource, now)?;
+        }
+        lines(f, "transition", &self.transitions, transition)?;
+        lines(f, "overlay", &self.overlays, overlay)
+    }
+}
+
+#[cfg(test)]
+mod diff_test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const GAME: &str = "resource: [Save, Audio] state: [Title, Game, Pause, GameOver] \
+        intermediate: [Start] reference: [Game -< mut Save, Pause -< Save] \
+        transition: [Title >- Start -> Game, Game >--> GameOver, GameOver >--> Title] \
+        overlay: [Game ^ Pause]";
+
+    fn diffed(old: &str, new: &str) -> FlowDiff {
+        let flow = |text| RawFlow::from(Flow::from_str(text).unwrap());
+        diff(&flow(old), &flow(new))
+    }
+
+    #[test]
+    fn reordering_is_not_a_change() {
+        let reordered = "resource: [Audio, Save] state: [GameOver, Pause, Game, Title] \
+            intermediate: [Start] reference: [Pause -< Save, Game -< mut Save] \
+            transition: [GameOver >--> Title, Game >--> GameOver, Title >- Start -> Game] \
+            overlay: [Game ^ Pause]";
+        assert!(diffed(GAME, reordered).is_empty());
+    }
+
+    #[test]
+    fn renamed_states_are_detected() {
+        let renamed = GAME.replace("Game,", "Play,").replace("Game ", "Play ");
+        let diff = diffed(GAME, &renamed);
+        assert_eq!(
+            diff.renamed_states,
+            [Rename {
+                from: "Game".to_string(),
+                to: "Play".to_string()
+            }]
+        );
+        assert_eq!(diff.to_string(), "~ state Game -> Play\n");
+    }
+
+    #[test]
+    fn unconnected_states_are_not_renamed() {
+        let old = GAME.replace("GameOver]", "GameOver, Credits]");
+        let new = GAME.replace("GameOver]", "GameOver, Menu]");
+        assert_eq!(
+            diffed(&old, &new).to_string(),
+            "- state Credits\n+ state Menu\n"
+        );
+    }
+
+    #[test]
+    fn changes_are_listed() {
+        let changed = "resource: [Save] state: [Title, Game, Pause, GameOver, Menu] \
+            intermediate: [] reference: [Game -< Save, Pause -< Save, Menu -< Save] \
+            transition: [Title >--> Game, Game >--> GameOver, GameOver >--> Title] \
+            overlay: []";
+        assert_eq!(
+            diffed(GAME, changed).to_string(),
+            "+ state Menu\n\
+            - resource Audio\n\
+            - intermediate Start\n\
+            + reference Menu -< Save\n\
+            ~ reference Game -< Save is now immutable\n\
+            - transition Title >- Start -> Game\n\
+            + transition Title >--> Game\n\
+            - overlay Game ^ Pause\n"
+        );
+    }
+
+    #[test]
+    fn json_names_match_flow_names() {
+        let text = "resource: [] state: [game::Level<1>] intermediate: [] reference: [] \
+            transition: [] overlay: []";
+        let mut json = RawFlow::from(Flow::from_str(text).unwrap());
+        json.states[0].0 = "game::Level<1>".to_string();
+        assert!(diff(&json, &Flow::from_str(text).unwrap().into()).is_empty());
+    }
+
+    #[test]
+    fn diffs_serialize_to_json() {
+        let json = serde_json::to_value(diffed(GAME, &GAME.replace(", Audio", ""))).unwrap();
+        assert_eq!(json["resources"]["removed"], serde_json::json!(["Audio"]));
+        assert_eq!(json["states"]["added"], serde_json::json!([]));
+    }
+}
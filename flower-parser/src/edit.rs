@@ -0,0 +1,780 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::arrange::NodeKind;
+use super::ast::State;
+use super::raw_ast::{
+    RawFlow, RawIntermediate, RawLayoutNode, RawOverlay, RawReference, RawResource, RawState,
+    RawTransition,
+};
+
+/// An editing operation. Applying one returns its inverse, which undoes it exactly. Entries
+/// are added at `index`, or at the end when it is not given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Edit {
+    AddElement {
+        kind: NodeKind,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        index: Option<usize>,
+    },
+    /// Also removes the references, transitions, overlays and layout that mention it.
+    RemoveElement {
+        kind: NodeKind,
+        name: String,
+    },
+    /// Also renames it in the references, transitions, overlays and layout.
+    RenameElement {
+        kind: NodeKind,
+        from: String,
+        to: String,
+    },
+    AddReference {
+        reference: RawReference,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        index: Option<usize>,
+    },
+    RemoveReference {
+        reference: RawReference,
+    },
+    ToggleMutable {
+        state: String,
+        resource: String,
+    },
+    AddTransition {
+        transition: RawTransition,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        index: Option<usize>,
+    },
+    RemoveTransition {
+        transition: RawTransition,
+    },
+    AddOverlay {
+        overlay: RawOverlay,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        index: Option<usize>,
+    },
+    RemoveOverlay {
+        overlay: RawOverlay,
+    },
+    /// Sets or, with no `node`, clears where the editor draws an element.
+    SetLayout {
+        name: String,
+        node: Option<RawLayoutNode>,
+    },
+    /// Puts back entries that removing an element took with it, at their original indices.
+    /// Unlike the `add_*` edits it does not require the names they mention to be declared.
+    RestoreEntries {
+        entries: Entries,
+    },
+    /// Takes out the entries at the given indices, undoing `restore_entries`.
+    TakeEntries {
+        entries: Entries,
+    },
+    /// Applies the edits in order; the inverse undoes them in reverse.
+    Batch {
+        edits: Vec<Edit>,
+    },
+}
+
+/// Entries paired with their indices in their section, in increasing order of index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Entries {
+    pub references: Vec<(usize, RawReference)>,
+    pub transitions: Vec<(usize, RawTransition)>,
+    pub overlays: Vec<(usize, RawOverlay)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidName(String),
+    AlreadyExists(String),
+    NotFound(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "`{}` is not a type path", name),
+            Error::AlreadyExists(what) => write!(f, "{} already exists", what),
+            Error::NotFound(what) => write!(f, "{} does not exist", what),
+        }
+    }
+}
+
+fn kind_name(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::Resource => "resource",
+        NodeKind::State => "state",
+        NodeKind::Intermediate => "intermediate",
+    }
+}
+
+fn validate(name: &str) -> Result<(), Error> {
+    match State::try_from(RawState(name.to_string())) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::InvalidName(name.to_string())),
+    }
+}
+
+fn names(flow: &mut RawFlow, kind: NodeKind) -> Vec<&mut String> {
+    match kind {
+        NodeKind::Resource => flow.resources.iter_mut().map(|r| &mut r.0).collect(),
+        NodeKind::State => flow.states.iter_mut().map(|s| &mut s.0).collect(),
+        NodeKind::Intermediate => flow.intermediates.iter_mut().map(|i| &mut i.0).collect(),
+    }
+}
+
+// Every name in the other sections that refers to an element of `kind`.
+fn mentions(flow: &mut RawFlow, kind: NodeKind) -> Vec<&mut String> {
+    let RawFlow {
+        references,
+        transitions,
+        overlays,
+        ..
+    } = flow;
+    match kind {
+        NodeKind::Resource => references.iter_mut().map(|r| &mut r.resource.0).collect(),
+        NodeKind::State => references
+            .iter_mut()
+            .map(|r| &mut r.state.0)
+            .chain(
+                transitions
+                    .iter_mut()
+                    .flat_map(|t| [&mut t.from.0, &mut t.to.0]),
+            )
+            .chain(
+                overlays
+                    .iter_mut()
+                    .flat_map(|o| [&mut o.back.0, &mut o.front.0]),
+            )
+            .collect(),
+        NodeKind::Intermediate => transitions
+            .iter_mut()
+            .filter_map(|t| t.intermediate.as_mut().map(|i| &mut i.0))
+            .collect(),
+    }
+}
+
+fn exists(flow: &mut RawFlow, kind: NodeKind, name: &str) -> bool {
+    names(flow, kind).iter().any(|n| **n == name)
+}
+
+// Whether any element, of any kind, is called `name`.
+fn named(flow: &mut RawFlow, name: &str) -> bool {
+    [NodeKind::Resource, NodeKind::State, NodeKind::Intermediate]
+        .into_iter()
+        .any(|kind| exists(flow, kind, name))
+}
+
+fn require(flow: &mut RawFlow, kind: NodeKind, name: &str) -> Result<(), Error> {
+    match exists(flow, kind, name) {
+        true => Ok(()),
+        false => Err(Error::NotFound(format!("{} `{}`", kind_name(kind), name))),
+    }
+}
+
+fn insert<T>(entries: &mut Vec<T>, entry: T, index: Option<usize>) {
+    let index = index.unwrap_or(entries.len()).min(entries.len());
+    entries.insert(index, entry);
+}
+
+fn remove<T: PartialEq>(entries: &mut Vec<T>, entry: &T, what: String) -> Result<usize, Error> {
+    let index = entries
+        .iter()
+        .position(|e| e == entry)
+        .ok_or(Error::NotFound(what))?;
+    entries.remove(index);
+    Ok(index)
+}
+
+// Removes the entries matching `mentions`, returning them with the indices they had.
+fn remove_where<T>(entries: &mut Vec<T>, mentions: impl Fn(&T) -> bool) -> Vec<(usize, T)> {
+    let mut removed = Vec::new();
+    for (index, entry) in std::mem::take(entries).into_iter().enumerate() {
+        if mentions(&entry) {
+            removed.push((index, entry));
+        } else {
+            entries.push(entry);
+        }
+    }
+    removed
+}
+
+fn restore<T: Clone>(entries: &mut Vec<T>, restored: &[(usize, T)]) {
+    for (index, entry) in restored {
+        insert(entries, entry.clone(), Some(*index));
+    }
+}
+
+// Checks every entry before taking any, so that `flow` is unchanged on error.
+fn take<T: PartialEq>(
+    entries: &mut Vec<T>,
+    taken: &[(usize, T)],
+    what: impl Fn(&T) -> String,
+) -> Result<(), Error> {
+    for (index, entry) in taken {
+        if entries.get(*index) != Some(entry) {
+            return Err(Error::NotFound(what(entry)));
+        }
+    }
+    for (index, _) in taken.iter().rev() {
+        entries.remove(*index);
+    }
+    Ok(())
+}
+
+fn set_layout(flow: &mut RawFlow, name: String, node: Option<RawLayoutNode>) -> Edit {
+    let layout = flow.layout.get_or_insert_with(BTreeMap::new);
+    let previous = match node {
+        Some(node) => layout.insert(name.clone(), node),
+        None => layout.remove(&name),
+    };
+    if layout.is_empty() {
+        flow.layout = None;
+    }
+    Edit::SetLayout {
+        name,
+        node: previous,
+    }
+}
+
+fn reference_name(r: &RawReference) -> String {
+    format!("reference `{} -< {}`", r.state.0, r.resource.0)
+}
+
+fn transition_name(t: &RawTransition) -> String {
+    format!("transition `{} -> {}`", t.from.0, t.to.0)
+}
+
+fn overlay_name(o: &RawOverlay) -> String {
+    format!("overlay `{} ^ {}`", o.back.0, o.front.0)
+}
+
+// Applies `edit` to `flow` and returns the edit that undoes it. On error `flow` is unchanged.
+pub fn apply(flow: &mut RawFlow, edit: Edit) -> Result<Edit, Error> {
+    let inverse = match edit {
+        Edit::AddElement { kind, name, index } => {
+            validate(&name)?;
+            if exists(flow, kind, &name) {
+                return Err(Error::AlreadyExists(format!(
+                    "{} `{}`",
+                    kind_name(kind),
+                    name
+                )));
+            }
+            match kind {
+                NodeKind::Resource => insert(&mut flow.resources, RawResource(name.clone()), index),
+                NodeKind::State => insert(&mut flow.states, RawState(name.clone()), index),
+                NodeKind::Intermediate => insert(
+                    &mut flow.intermediates,
+                    RawIntermediate(name.clone()),
+                    index,
+                ),
+            }
+            Edit::RemoveElement { kind, name }
+        }
+        Edit::RemoveElement { kind, name } => {
+            require(flow, kind, &name)?;
+            let at = names(flow, kind).iter().position(|n| **n == name).unwrap();
+            let mut restore = vec![Edit::AddElement {
+                kind,
+                name: name.clone(),
+                index: Some(at),
+            }];
+            match kind {
+                NodeKind::Resource => {
+                    flow.resources.remove(at);
+                }
+                NodeKind::State => {
+                    flow.states.remove(at);
+                }
+                NodeKind::Intermediate => {
+                    flow.intermediates.remove(at);
+                }
+            }
+            let n = name.as_str();
+            let entries = Entries {
+                references: remove_where(&mut flow.references, |r| match kind {
+                    NodeKind::Resource => r.resource.0 == n,
+                    NodeKind::State => r.state.0 == n,
+                    NodeKind::Intermediate => false,
+                }),
+                transitions: remove_where(&mut flow.transitions, |t| match kind {
+                    NodeKind::Resource => false,
+                    NodeKind::State => t.from.0 == n || t.to.0 == n,
+                    NodeKind::Intermediate => t.intermediate.as_ref().is_some_and(|i| i.0 == n),
+                }),
+                overlays: remove_where(&mut flow.overlays, |o| {
+                    kind == NodeKind::State && (o.back.0 == n || o.front.0 == n)
+                }),
+            };
+            // The entries may mention other undeclared names, so they are put back without
+            // the checks that `add_reference` and the like make.
+            if entries != Entries::default() {
+                restore.push(Edit::RestoreEntries { entries });
+            }
+            let laid_out = flow.layout.as_ref().is_some_and(|l| l.contains_key(n));
+            if laid_out && !named(flow, n) {
+                restore.push(set_layout(flow, name, None));
+            }
+            Edit::Batch { edits: restore }
+        }
+        Edit::RenameElement { kind, from, to } => {
+            validate(&to)?;
+            require(flow, kind, &from)?;
+            // A mention of `to` that is not declared would be renamed back on undo too.
+            let used = exists(flow, kind, &to) || mentions(flow, kind).iter().any(|n| **n == to);
+            if from != to && used {
+                return Err(Error::AlreadyExists(format!(
+                    "{} `{}`",
+                    kind_name(kind),
+                    to
+                )));
+            }
+            let taken = from == to || named(flow, &to);
+            let rename = |name: &mut String| {
+                if *name == from {
+                    *name = to.clone();
+                }
+            };
+            names(flow, kind).into_iter().for_each(rename);
+            mentions(flow, kind).into_iter().for_each(rename);
+            let rename_back = Edit::RenameElement {
+                kind,
+                from: to.clone(),
+                to: from.clone(),
+            };
+            // The layout is keyed by name alone, so it stays put when another element shares
+            // either name.
+            let shared = taken || named(flow, &from);
+            let node = match shared {
+                true => None,
+                false => flow.layout.as_mut().and_then(|l| l.remove(&from)),
+            };
+            match node {
+                Some(node) => {
+                    let restore = set_layout(flow, to, Some(node));
+                    Edit::Batch {
+                        edits: vec![rename_back, restore],
+                    }
+                }
+                None => rename_back,
+            }
+        }
+        Edit::AddReference { reference, index } => {
+            require(flow, NodeKind::State, &reference.state.0)?;
+            require(flow, NodeKind::Resource, &reference.resource.0)?;
+            insert(&mut flow.references, reference.clone(), index);
+            Edit::RemoveReference { reference }
+        }
+        Edit::RemoveReference { reference } => {
+            let what = reference_name(&reference);
+            let index = remove(&mut flow.references, &reference, what)?;
+            Edit::AddReference {
+                reference,
+                index: Some(index),
+            }
+        }
+        Edit::ToggleMutable { state, resource } => {
+            let reference = flow
+                .references
+                .iter_mut()
+                .find(|r| r.state.0 == state && r.resource.0 == resource)
+                .ok_or_else(|| Error::NotFound(format!("reference `{} -< {}`", state, resource)))?;
+            reference.mutable = !reference.mutable;
+            Edit::ToggleMutable { state, resource }
+        }
+        Edit::AddTransition { transition, index } => {
+            require(flow, NodeKind::State, &transition.from.0)?;
+            require(flow, NodeKind::State, &transition.to.0)?;
+            if let Some(intermediate) = &transition.intermediate {
+                require(flow, NodeKind::Intermediate, &intermediate.0)?;
+            }
+            insert(&mut flow.transitions, transition.clone(), index);
+            Edit::RemoveTransition { transition }
+        }
+        Edit::RemoveTransition { transition } => {
+            let what = transition_name(&transition);
+            let index = remove(&mut flow.transitions, &transition, what)?;
+            Edit::AddTransition {
+                transition,
+                index: Some(index),
+            }
+        }
+        Edit::AddOverlay { overlay, index } => {
+            require(flow, NodeKind::State, &overlay.back.0)?;
+            require(flow, NodeKind::State, &overlay.front.0)?;
+            insert(&mut flow.overlays, overlay.clone(), index);
+            Edit::RemoveOverlay { overlay }
+        }
+        Edit::RemoveOverlay { overlay } => {
+            let what = overlay_name(&overlay);
+            let index = remove(&mut flow.overlays, &overlay, what)?;
+            Edit::AddOverlay {
+                overlay,
+                index: Some(index),
+            }
+        }
+        Edit::RestoreEntries { entries } => {
+            restore(&mut flow.references, &entries.references);
+            restore(&mut flow.transitions, &entries.transitions);
+            restore(&mut flow.overlays, &entries.overlays);
+            Edit::TakeEntries { entries }
+        }
+        Edit::TakeEntries { entries } => {
+            let before = flow.clone();
+            let taken = take(&mut flow.references, &entries.references, reference_name)
+                .and_then(|()| take(&mut flow.transitions, &entries.transitions, transition_name))
+                .and_then(|()| take(&mut flow.overlays, &entries.overlays, overlay_name));
+            if let Err(e) = taken {
+                *flow = before;
+                return Err(e);
+            }
+            Edit::RestoreEntries { entries }
+        }
+        Edit::SetLayout { name, node } => set_layout(flow, name, node),
+        Edit::Batch { edits } => {
+            let before = flow.clone();
+            let mut inverses = Vec::new();
+            for edit in edits {
+                match apply(flow, edit) {
+                    Ok(inverse) => inverses.push(inverse),
+                    Err(e) => {
+                        *flow = before;
+                        return Err(e);
+                    }
+                }
+            }
+            inverses.reverse();
+            Edit::Batch { edits: inverses }
+        }
+    };
+    Ok(inverse)
+}
+
+// Undo and redo stacks of inverse edits.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn apply(&mut self, flow: &mut RawFlow, edit: Edit) -> Result<(), Error> {
+        let inverse = apply(flow, edit)?;
+        self.undo.push(inverse);
+        self.redo.clear();
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // Returns false when there is nothing to undo.
+    pub fn undo(&mut self, flow: &mut RawFlow) -> Result<bool, Error> {
+        let edit = match self.undo.last() {
+            Some(edit) => edit.clone(),
+            None => return Ok(false),
+        };
+        let inverse = apply(flow, edit)?;
+        self.undo.pop();
+        self.redo.push(inverse);
+        Ok(true)
+    }
+
+    // Returns false when there is nothing to redo.
+    pub fn redo(&mut self, flow: &mut RawFlow) -> Result<bool, Error> {
+        let edit = match self.redo.last() {
+            Some(edit) => edit.clone(),
+            None => return Ok(false),
+        };
+        let inverse = apply(flow, edit)?;
+        self.redo.pop();
+        self.undo.push(inverse);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod edit_test {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::arbitrary::raw_flow;
+    use crate::Flow;
+
+    const GAME: &str = "#[layout(Title { x: 0, y: 0 }, Game { x: 200, y: 0 })] \
+        resource: [Save, Audio] state: [Title, Game, Pause] intermediate: [Start] \
+        reference: [Title -< Audio, Game -< mut Save, Game -< Audio] \
+        transition: [Title >- Start -> Game, Game >--> Title] overlay: [Game ^ Pause]";
+
+    fn flow(text: &str) -> RawFlow {
+        Flow::from_str(text).unwrap().into()
+    }
+
+    fn state(name: &str) -> RawState {
+        RawState(name.to_string())
+    }
+
+    // Applies `edit`, checks the result, then checks that the inverse restores the flow.
+    fn check(before: &str, edit: Edit, after: &str) {
+        let mut edited = flow(before);
+        let inverse = apply(&mut edited, edit).unwrap();
+        assert_eq!(edited, flow(after));
+        apply(&mut edited, inverse).unwrap();
+        assert_eq!(edited, flow(before));
+    }
+
+    #[test]
+    fn removing_a_state_cascades() {
+        check(
+            GAME,
+            Edit::RemoveElement {
+                kind: NodeKind::State,
+                name: "Game".to_string(),
+            },
+            "#[layout(Title { x: 0, y: 0 })] \
+            resource: [Save, Audio] state: [Title, Pause] intermediate: [Start] \
+            reference: [Title -< Audio] transition: [] overlay: []",
+        );
+    }
+
+    #[test]
+    fn removing_an_intermediate_removes_its_transitions() {
+        check(
+            GAME,
+            Edit::RemoveElement {
+                kind: NodeKind::Intermediate,
+                name: "Start".to_string(),
+            },
+            "#[layout(Title { x: 0, y: 0 }, Game { x: 200, y: 0 })] \
+            resource: [Save, Audio] state: [Title, Game, Pause] intermediate: [] \
+            reference: [Title -< Audio, Game -< mut Save, Game -< Audio] \
+            transition: [Game >--> Title] overlay: [Game ^ Pause]",
+        );
+    }
+
+    #[test]
+    fn renaming_a_state_cascades() {
+        check(
+            GAME,
+            Edit::RenameElement {
+                kind: NodeKind::State,
+                from: "Game".to_string(),
+                to: "Play".to_string(),
+            },
+            "#[layout(Title { x: 0, y: 0 }, Play { x: 200, y: 0 })] \
+            resource: [Save, Audio] state: [Title, Play, Pause] \
+            intermediate: [Start] \
+            reference: [Title -< Audio, Play -< mut Save, Play -< Audio] \
+            transition: [Title >- Start -> Play, Play >--> Title] \
+            overlay: [Play ^ Pause]",
+        );
+    }
+
+    #[test]
+    fn references_and_overlays_can_be_edited() {
+        check(
+            GAME,
+            Edit::Batch {
+                edits: vec![
+                    Edit::ToggleMutable {
+                        state: "Game".to_string(),
+                        resource: "Save".to_string(),
+                    },
+                    Edit::AddReference {
+                        reference: RawReference {
+                            state: state("Pause"),
+                            mutable: true,
+                            resource: RawResource("Audio".to_string()),
+                        },
+                        index: Some(0),
+                    },
+                    Edit::RemoveOverlay {
+                        overlay: RawOverlay {
+                            back: state("Game"),
+                            front: state("Pause"),
+                        },
+                    },
+                ],
+            },
+            "#[layout(Title { x: 0, y: 0 }, Game { x: 200, y: 0 })] \
+            resource: [Save, Audio] state: [Title, Game, Pause] intermediate: [Start] \
+            reference: [Pause -< mut Audio, Title -< Audio, Game -< Save, Game -< Audio] \
+            transition: [Title >- Start -> Game, Game >--> Title] overlay: []",
+        );
+    }
+
+    #[test]
+    fn invalid_edits_leave_the_flow_unchanged() {
+        let mut edited = flow(GAME);
+        let errors = [
+            Edit::AddElement {
+                kind: NodeKind::State,
+                name: "Game".to_string(),
+                index: None,
+            },
+            Edit::AddElement {
+                kind: NodeKind::Resource,
+                name: "not a type".to_string(),
+                index: None,
+            },
+            Edit::RenameElement {
+                kind: NodeKind::Resource,
+                from: "Score".to_string(),
+                to: "Points".to_string(),
+            },
+            Edit::AddTransition {
+                transition: RawTransition {
+                    from: state("Pause"),
+                    intermediate: None,
+                    after_ms: None,
+                    to: state("Menu"),
+                },
+                index: None,
+            },
+            Edit::Batch {
+                edits: vec![
+                    Edit::RemoveElement {
+                        kind: NodeKind::State,
+                        name: "Pause".to_string(),
+                    },
+                    Edit::RemoveElement {
+                        kind: NodeKind::State,
+                        name: "Pause".to_string(),
+                    },
+                ],
+            },
+        ]
+        .map(|edit| apply(&mut edited, edit).unwrap_err().to_string());
+        assert_eq!(
+            errors,
+            [
+                "state `Game` already exists",
+                "`not a type` is not a type path",
+                "resource `Score` does not exist",
+                "state `Menu` does not exist",
+                "state `Pause` does not exist",
+            ]
+        );
+        assert_eq!(edited, flow(GAME));
+    }
+
+    #[test]
+    fn history_undoes_and_redoes() {
+        let mut edited = flow(GAME);
+        let mut history = History::new();
+        history
+            .apply(
+                &mut edited,
+                Edit::AddElement {
+                    kind: NodeKind::State,
+                    name: "Menu".to_string(),
+                    index: Some(1),
+                },
+            )
+            .unwrap();
+        let added = edited.clone();
+        assert_eq!(edited.states[1], state("Menu"));
+
+        assert!(history.undo(&mut edited).unwrap());
+        assert_eq!(edited, flow(GAME));
+        assert!(!history.undo(&mut edited).unwrap());
+        assert!(history.redo(&mut edited).unwrap());
+        assert_eq!(edited, added);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn edits_round_trip_through_json() {
+        let edit = Edit::AddElement {
+            kind: NodeKind::Intermediate,
+            name: "Load".to_string(),
+            index: None,
+        };
+        let json = serde_json::to_string(&edit).unwrap();
+        assert_eq!(
+            json,
+            r#"{"op":"add_element","kind":"intermediate","name":"Load"}"#
+        );
+        assert_eq!(serde_json::from_str::<Edit>(&json).unwrap(), edit);
+    }
+
+    #[test]
+    fn undeclared_mentions_survive_undo() {
+        check(
+            "resource: [] state: [A] intermediate: [] reference: [A -< Missing] \
+            transition: [A >- Via -> Gone] overlay: []",
+            Edit::RemoveElement {
+                kind: NodeKind::State,
+                name: "A".to_string(),
+            },
+            "resource: [] state: [] intermediate: [] reference: [] transition: [] overlay: []",
+        );
+
+        let mut edited = flow(
+            "resource: [] state: [A] intermediate: [] reference: [] \
+            transition: [A >--> B] overlay: []",
+        );
+        let rename = Edit::RenameElement {
+            kind: NodeKind::State,
+            from: "A".to_string(),
+            to: "B".to_string(),
+        };
+        assert_eq!(
+            apply(&mut edited, rename).unwrap_err().to_string(),
+            "state `B` already exists"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn inverses_restore_the_flow(flow in raw_flow()) {
+            let kinds = [NodeKind::Resource, NodeKind::State, NodeKind::Intermediate];
+            for kind in kinds {
+                let mut targets = vec!["Renamed".to_string()];
+                targets.extend(mentions(&mut flow.clone(), kind).into_iter().map(|n| n.clone()));
+                for name in names(&mut flow.clone(), kind).into_iter().map(|n| n.clone()) {
+                    let renames = targets.iter().map(|to| Edit::RenameElement {
+                        kind,
+                        from: name.clone(),
+                        to: to.clone(),
+                    });
+                    let removal = Edit::RemoveElement { kind, name: name.clone() };
+                    for edit in renames.chain([removal]) {
+                        let mut edited = flow.clone();
+                        match apply(&mut edited, edit) {
+                            Ok(inverse) => {
+                                let redo = apply(&mut edited, inverse).unwrap();
+                                prop_assert_eq!(&edited, &flow);
+                                apply(&mut edited, redo).unwrap();
+                            }
+                            Err(_) => prop_assert_eq!(&edited, &flow),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
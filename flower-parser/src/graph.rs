@@ -0,0 +1,108 @@
+use super::interpreter::{Borrow, Step};
+use super::raw_ast::{RawFlow, RawOverlay, RawReference, RawTransition};
+
+// Name-based lookups over a `RawFlow`, shared by the tools that walk a flow without compiling it.
+#[derive(Clone, Copy)]
+pub struct FlowGraph<'a> {
+    flow: &'a RawFlow,
+}
+
+impl<'a> FlowGraph<'a> {
+    pub fn new(flow: &'a RawFlow) -> Self {
+        FlowGraph { flow }
+    }
+
+    pub fn flow(&self) -> &'a RawFlow {
+        self.flow
+    }
+
+    pub fn has_state(&self, state: &str) -> bool {
+        self.flow.states.iter().any(|s| s.0 == state)
+    }
+
+    pub fn references_of<'s>(self, state: &'s str) -> impl Iterator<Item = &'a RawReference> + 's
+    where
+        'a: 's,
+    {
+        self.flow
+            .references
+            .iter()
+            .filter(move |r| r.state.0 == state)
+    }
+
+    pub fn transitions_from<'s>(
+        self,
+        state: &'s str,
+    ) -> impl Iterator<Item = &'a RawTransition> + 's
+    where
+        'a: 's,
+    {
+        self.flow
+            .transitions
+            .iter()
+            .filter(move |t| t.from.0 == state)
+    }
+
+    pub fn overlays_on<'s>(self, back: &'s str) -> impl Iterator<Item = &'a RawOverlay> + 's
+    where
+        'a: 's,
+    {
+        self.flow.overlays.iter().filter(move |o| o.back.0 == back)
+    }
+
+    // Same rule as the runtime: a shared resource conflicts when either side borrows it mutably.
+    pub fn conflicts(self, a: &str, b: &str) -> bool {
+        self.references_of(a).any(|ra| {
+            self.references_of(b)
+                .any(|rb| ra.resource == rb.resource && (ra.mutable || rb.mutable))
+        })
+    }
+
+    // Index of the lowest stack entry that runs together with the top one.
+    pub fn running_from(self, stack: &[String]) -> usize {
+        let mut start = stack.len().saturating_sub(1);
+        while start > 0 {
+            let below = &stack[start - 1];
+            if stack[start..].iter().any(|s| self.conflicts(s, below)) {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    pub fn enabled(self, stack: &[String]) -> Vec<Step> {
+        let top = match stack.last() {
+            Some(top) => top,
+            None => return Vec::new(),
+        };
+        let transitions = self.transitions_from(top).map(|t| Step::Transition {
+            from: t.from.0.clone(),
+            intermediate: t.intermediate.as_ref().map(|i| i.0.clone()),
+            to: t.to.0.clone(),
+            after_ms: t.after_ms,
+        });
+        let pushes = self.overlays_on(top).map(|o| Step::Push {
+            back: o.back.0.clone(),
+            front: o.front.0.clone(),
+        });
+        let pop = Step::Pop { front: top.clone() };
+        transitions.chain(pushes).chain(Some(pop)).collect()
+    }
+
+    pub fn borrows(self, stack: &[String]) -> Vec<Borrow> {
+        let running_from = self.running_from(stack);
+        stack
+            .iter()
+            .enumerate()
+            .flat_map(|(index, state)| {
+                self.references_of(state).map(move |r| Borrow {
+                    state: state.clone(),
+                    resource: r.resource.0.clone(),
+                    mutable: r.mutable,
+                    active: index >= running_from,
+                })
+            })
+            .collect()
+    }
+}
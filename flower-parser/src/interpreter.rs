@@ -0,0 +1,221 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::graph::FlowGraph;
+use super::raw_ast::RawFlow;
+use super::trace::{Trace, TraceEntry};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    UnknownState(String),
+    NotEnabled(Step),
+    NotOnStack(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownState(state) => write!(f, "state `{}` is not declared", state),
+            Error::NotEnabled(step) => write!(f, "step is not enabled: {}", step),
+            Error::NotOnStack(state) => write!(f, "state `{}` is not on the stack", state),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Step {
+    Transition {
+        from: String,
+        intermediate: Option<String>,
+        to: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after_ms: Option<u64>,
+    },
+    Push {
+        back: String,
+        front: String,
+    },
+    Pop {
+        front: String,
+    },
+}
+
+impl Step {
+    pub fn apply_to(&self, stack: &mut Vec<String>) {
+        match self {
+            Step::Transition { to, .. } => {
+                stack.pop();
+                stack.push(to.clone());
+            }
+            Step::Push { front, .. } => stack.push(front.clone()),
+            Step::Pop { .. } => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Transition {
+                from,
+                intermediate,
+                to,
+                ..
+            } => match intermediate {
+                Some(intermediate) => write!(f, "{} >- {} -> {}", from, intermediate, to),
+                None => write!(f, "{} >--> {}", from, to),
+            },
+            Step::Push { back, front } => write!(f, "push {} ^ {}", back, front),
+            Step::Pop { front } => write!(f, "pop {}", front),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Borrow {
+    pub state: String,
+    pub resource: String,
+    pub mutable: bool,
+    // Whether the state runs this step; covered states keep their borrows but are suspended.
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub stack: Vec<String>,
+    pub borrows: Vec<Borrow>,
+}
+
+// Steps through a flow by name only, following the same stack rules as `flower-runtime`.
+pub struct Interpreter {
+    flow: RawFlow,
+    stack: Vec<String>,
+    trace: Trace,
+}
+
+impl Interpreter {
+    pub fn new(flow: RawFlow, initial: &str) -> Result<Self, Error> {
+        if !FlowGraph::new(&flow).has_state(initial) {
+            return Err(Error::UnknownState(initial.to_string()));
+        }
+        let trace = Trace(vec![TraceEntry::Start {
+            state: initial.to_string(),
+        }]);
+        Ok(Interpreter {
+            flow,
+            stack: vec![initial.to_string()],
+            trace,
+        })
+    }
+
+    pub fn flow(&self) -> &RawFlow {
+        &self.flow
+    }
+
+    pub fn stack(&self) -> &[String] {
+        &self.stack
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    pub fn enabled(&self) -> Vec<Step> {
+        FlowGraph::new(&self.flow).enabled(&self.stack)
+    }
+
+    pub fn apply(&mut self, step: &Step) -> Result<Report, Error> {
+        if !self.enabled().contains(step) {
+            return Err(Error::NotEnabled(step.clone()));
+        }
+        step.apply_to(&mut self.stack);
+        self.trace.0.push(TraceEntry::Step { step: step.clone() });
+        Ok(self.report())
+    }
+
+    pub fn report(&self) -> Report {
+        Report {
+            stack: self.stack.clone(),
+            borrows: FlowGraph::new(&self.flow).borrows(&self.stack),
+        }
+    }
+}
+
+#[cfg(test)]
+mod interpreter_test {
+    use std::str::FromStr;
+
+    use super::{Borrow, Error, Interpreter, Step};
+    use crate::{Flow, RawFlow};
+
+    const FLOW_STR: &str = r#"resource: [Save, Audio]
+state: [Title, Game, Pause]
+intermediate: [Start]
+reference: [Game -< mut Save, Game -< Audio, Pause -< Audio]
+transition: [Title >- Start -> Game, Game >--> Title after 3s]
+overlay: [Game ^ Pause]
+"#;
+
+    fn interpreter() -> Interpreter {
+        let flow = Flow::from_str(FLOW_STR).unwrap();
+        Interpreter::new(RawFlow::from(flow), "Title").unwrap()
+    }
+
+    fn borrow(state: &str, resource: &str, mutable: bool, active: bool) -> Borrow {
+        Borrow {
+            state: state.to_string(),
+            resource: resource.to_string(),
+            mutable,
+            active,
+        }
+    }
+
+    #[test]
+    fn click_through() {
+        let mut interpreter = interpreter();
+        let enabled = interpreter.enabled();
+        assert_eq!(
+            enabled.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            ["Title >- Start -> Game", "pop Title"]
+        );
+
+        let report = interpreter.apply(&enabled[0]).unwrap();
+        assert_eq!(report.stack, ["Game"]);
+
+        let push = Step::Push {
+            back: "Game".to_string(),
+            front: "Pause".to_string(),
+        };
+        let report = interpreter.apply(&push).unwrap();
+        assert_eq!(report.stack, ["Game", "Pause"]);
+        assert_eq!(
+            report.borrows,
+            [
+                borrow("Game", "Save", true, true),
+                borrow("Game", "Audio", false, true),
+                borrow("Pause", "Audio", false, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_disabled_step() {
+        let mut interpreter = interpreter();
+        let push = Step::Push {
+            back: "Game".to_string(),
+            front: "Pause".to_string(),
+        };
+        assert_eq!(
+            interpreter.apply(&push),
+            Err(Error::NotEnabled(push.clone()))
+        );
+        assert!(matches!(
+            Interpreter::new(interpreter.flow().clone(), "Nowhere"),
+            Err(Error::UnknownState(_))
+        ));
+    }
+}
@@ -1,37 +1,58 @@
+#[cfg(test)]
+mod arbitrary;
+pub mod arrange;
 mod ast;
+pub mod checker;
+pub mod coverage;
+pub mod diagnostic;
+pub mod diff;
+pub mod edit;
+pub mod graph;
+pub mod interpreter;
+pub mod merge;
+pub mod paths;
+mod pretty;
 mod raw_ast;
+pub mod rename;
+pub mod schema;
+pub mod symbols;
+pub mod trace;
+#[cfg(test)]
+mod typescript;
 
-pub use ast::{Flow, Intermediate, Overlay, Reference, Resource, State, Transition};
-use proc_macro2::TokenStream;
+pub use ast::{
+    Delay, Flow, Intermediate, Layout, LayoutField, LayoutNode, Overlay, ParseError, Reference,
+    Resource, State, Transition,
+};
+use diagnostic::Outcome;
+use edit::{Edit, History};
+use interpreter::Interpreter;
 use quote::ToTokens;
-pub use raw_ast::RawFlow;
+pub use raw_ast::{
+    RawFlow, RawIntermediate, RawLayoutNode, RawOverlay, RawReference, RawResource, RawState,
+    RawTransition,
+};
+use schema::{Document, Mode};
 use std::str::FromStr;
-use syn::parse2;
+use trace::Trace;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::throw_str;
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn flow2json(flowString: String) -> String {
-    let input = TokenStream::from_str(&flowString).unwrap_or_else(|e| {
-        let error_message = format!("{}", e);
-        throw_str(&error_message)
-    });
-    let flow: Flow = parse2(input).unwrap_or_else(|e| {
-        let error_message = format!("{}", e);
-        throw_str(&error_message)
-    });
-    let raw_flow: RawFlow = flow.into();
-    serde_json::to_string(&raw_flow).unwrap_or_else(|e| {
-        let error_message = format!("{}", e);
-        throw_str(&error_message)
-    })
+    let raw_flow = Flow::from_str(&flowString)
+        .map(RawFlow::from)
+        .map_err(|e| e.to_string());
+    proc_macro2::extra::invalidate_current_thread_spans();
+    let raw_flow = raw_flow.unwrap_or_else(|error_message| throw_str(&error_message));
+    schema::to_string(&raw_flow)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn json2flow(jsonString: String) -> String {
-    let raw_flow: RawFlow = serde_json::from_str(&jsonString).unwrap_or_else(|e| {
+    let raw_flow: RawFlow = schema::from_str(&jsonString, Mode::Lenient).unwrap_or_else(|e| {
         let error_message = format!("{}", e);
         throw_str(&error_message)
     });
@@ -41,3 +62,171 @@ pub fn json2flow(jsonString: String) -> String {
     });
     flow.into_token_stream().to_string()
 }
+
+fn to_js<T: serde::Serialize>(value: &T) -> JsValue {
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    value.serialize(&serializer).unwrap()
+}
+
+fn flow_from_js(flow: JsValue) -> Result<RawFlow, String> {
+    let flow = serde_wasm_bindgen::from_value(flow).map_err(|e| e.to_string())?;
+    schema::from_value(flow, Mode::Lenient).map_err(|e| e.to_string())
+}
+
+// Returns `{ ok, value, diagnostics }` with the versioned `RawFlow` as `value`, instead of
+// throwing.
+#[wasm_bindgen(js_name = parseFlow)]
+pub fn parse_flow(text: String) -> JsValue {
+    to_js(&diagnostic::parse_flow(&text).map(Document::from))
+}
+
+// Returns `{ ok, value, diagnostics }` with the flow text as `value`, instead of throwing.
+#[wasm_bindgen(js_name = printFlow)]
+pub fn print_flow(flow: JsValue) -> JsValue {
+    let outcome = match flow_from_js(flow) {
+        Ok(flow) => diagnostic::print_flow(flow),
+        Err(e) => Outcome::error(e),
+    };
+    to_js(&outcome)
+}
+
+// Returns `{ ok, value, diagnostics }` with node and edge coordinates as `value`, instead of
+// throwing.
+#[wasm_bindgen(js_name = arrangeFlow)]
+pub fn arrange_flow(flow: JsValue) -> JsValue {
+    let outcome = match flow_from_js(flow) {
+        Ok(flow) => Outcome::ok(arrange::arrange(&flow)),
+        Err(e) => Outcome::error(e),
+    };
+    to_js(&outcome)
+}
+
+// Applies edits to a flow and keeps their inverses for undo and redo. Created with `load`;
+// `apply`, `undo` and `redo` return `{ ok, value, diagnostics }` with the versioned flow as
+// `value`; on error the flow is unchanged.
+#[wasm_bindgen]
+pub struct Editor {
+    flow: RawFlow,
+    history: History,
+}
+
+#[wasm_bindgen]
+impl Editor {
+    // Returns `{ ok, value, diagnostics }` with the editor as `value`, instead of throwing.
+    pub fn load(flow: JsValue) -> JsValue {
+        match flow_from_js(flow) {
+            Ok(flow) => {
+                let outcome = to_js(&Outcome::ok(()));
+                let editor = Editor {
+                    flow,
+                    history: History::new(),
+                };
+                js_sys::Reflect::set(&outcome, &"value".into(), &editor.into()).unwrap();
+                outcome
+            }
+            Err(e) => to_js(&Outcome::<()>::error(e)),
+        }
+    }
+
+    pub fn flow(&self) -> JsValue {
+        to_js(&Document::from(self.flow.clone()))
+    }
+
+    pub fn apply(&mut self, edit: JsValue) -> JsValue {
+        let outcome = match serde_wasm_bindgen::from_value::<Edit>(edit) {
+            Ok(edit) => self.outcome(|history, flow| history.apply(flow, edit)),
+            Err(e) => Outcome::error(e.to_string()),
+        };
+        to_js(&outcome)
+    }
+
+    pub fn undo(&mut self) -> JsValue {
+        to_js(&self.outcome(|history, flow| history.undo(flow).map(drop)))
+    }
+
+    pub fn redo(&mut self) -> JsValue {
+        to_js(&self.outcome(|history, flow| history.redo(flow).map(drop)))
+    }
+
+    #[wasm_bindgen(js_name = canUndo)]
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    #[wasm_bindgen(js_name = canRedo)]
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+}
+
+impl Editor {
+    fn outcome(
+        &mut self,
+        f: impl FnOnce(&mut History, &mut RawFlow) -> Result<(), edit::Error>,
+    ) -> Outcome<Document> {
+        match f(&mut self.history, &mut self.flow) {
+            Ok(()) => Outcome::ok(Document::from(self.flow.clone())),
+            Err(e) => Outcome::error(e.to_string()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct Simulator(Interpreter);
+
+#[wasm_bindgen]
+impl Simulator {
+    #[wasm_bindgen(constructor)]
+    #[allow(non_snake_case)]
+    pub fn new(jsonString: String, initial: String) -> Simulator {
+        let raw_flow: RawFlow = schema::from_str(&jsonString, Mode::Lenient).unwrap_or_else(|e| {
+            let error_message = format!("{}", e);
+            throw_str(&error_message)
+        });
+        let interpreter = Interpreter::new(raw_flow, &initial).unwrap_or_else(|e| {
+            let error_message = format!("{}", e);
+            throw_str(&error_message)
+        });
+        Simulator(interpreter)
+    }
+
+    pub fn report(&self) -> String {
+        serde_json::to_string(&self.0.report()).unwrap()
+    }
+
+    pub fn enabled(&self) -> String {
+        serde_json::to_string(&self.0.enabled()).unwrap()
+    }
+
+    pub fn trace(&self) -> String {
+        self.0.trace().to_json_lines()
+    }
+
+    pub fn apply(&mut self, index: usize) -> String {
+        let step = self.0.enabled().into_iter().nth(index).unwrap_or_else(|| {
+            let error_message = format!("no enabled step at index {}", index);
+            throw_str(&error_message)
+        });
+        let report = self.0.apply(&step).unwrap_or_else(|e| {
+            let error_message = format!("{}", e);
+            throw_str(&error_message)
+        });
+        serde_json::to_string(&report).unwrap()
+    }
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn replayTrace(jsonString: String, traceString: String) -> String {
+    let raw_flow: RawFlow = schema::from_str(&jsonString, Mode::Lenient).unwrap_or_else(|e| {
+        let error_message = format!("{}", e);
+        throw_str(&error_message)
+    });
+    let interpreter = Trace::from_json_lines(&traceString)
+        .and_then(|trace| trace.replay(&raw_flow))
+        .unwrap_or_else(|e| {
+            let error_message = format!("{}", e);
+            throw_str(&error_message)
+        });
+    serde_json::to_string(&interpreter.report()).unwrap()
+}
@@ -0,0 +1,485 @@
+use std::collections::BTreeMap;
+
+use super::diff::{canonical, diff, FlowDiff, Rename};
+use super::pretty::{overlay, reference, transition, ty};
+use super::raw_ast::{RawFlow, RawState};
+
+// The merged flow and the changes the two sides disagree on. Every conflict is resolved in
+// favour of ours, so the flow is usable either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    pub flow: RawFlow,
+    pub conflicts: Vec<String>,
+}
+
+fn change(base: Option<&str>, side: Option<&str>) -> String {
+    match (base, side) {
+        (None, Some(entry)) => format!("added as `{}`", entry),
+        (Some(_), None) => "removed".to_string(),
+        (_, Some(entry)) => format!("changed to `{}`", entry),
+        (None, None) => unreachable!(),
+    }
+}
+
+// Merges one section entry by entry, matching entries by `key`. An entry changed on only
+// one side takes that change. Ours comes first, in its order, then what theirs adds.
+fn entries<T: Clone + PartialEq, K: PartialEq>(
+    kind: &str,
+    [base, ours, theirs]: [&[T]; 3],
+    key: impl Fn(&T) -> K,
+    show: impl Fn(&T) -> String,
+    conflicts: &mut Vec<String>,
+) -> Vec<T> {
+    // The `n`th entry with a key matches the `n`th entry with that key on the other sides.
+    let keyed = |entries: &[T]| {
+        let mut keyed: Vec<((K, usize), T)> = Vec::new();
+        for e in entries {
+            let k = key(e);
+            let n = keyed.iter().filter(|((other, _), _)| *other == k).count();
+            keyed.push(((k, n), e.clone()));
+        }
+        keyed
+    };
+    let [base, ours, theirs] = [base, ours, theirs].map(keyed);
+    let find = |entries: &[((K, usize), T)], k: &(K, usize)| {
+        entries
+            .iter()
+            .find(|(other, _)| other == k)
+            .map(|(_, e)| e.clone())
+    };
+    let mut merge = |b: Option<T>, o: Option<T>, t: Option<T>| {
+        if o == t || t == b {
+            return o;
+        }
+        if o == b {
+            return t;
+        }
+        let [b_, o_, t_] = [&b, &o, &t].map(|e| e.as_ref().map(&show));
+        conflicts.push(format!(
+            "{} `{}` is {} by ours and {} by theirs",
+            kind,
+            b_.as_ref().or(o_.as_ref()).unwrap(),
+            change(b_.as_deref(), o_.as_deref()),
+            change(b_.as_deref(), t_.as_deref())
+        ));
+        o
+    };
+    let mut merged = Vec::new();
+    for (k, o) in &ours {
+        merged.extend(merge(find(&base, k), Some(o.clone()), find(&theirs, k)));
+    }
+    for (k, t) in &theirs {
+        if find(&ours, k).is_none() {
+            merged.extend(merge(find(&base, k), None, Some(t.clone())));
+        }
+    }
+    merged
+}
+
+// Whether a merged `entry` that mentions `element` is kept. When one side removes the
+// element and the other adds the entry, ours wins: its element comes back, or its removal
+// stands.
+fn survives<T: Clone + PartialEq>(
+    element: &T,
+    (kind, name): (&str, &str),
+    entry: &str,
+    merged: &mut Vec<T>,
+    [ours, theirs]: [&[T]; 2],
+    conflicts: &mut Vec<String>,
+) -> bool {
+    if merged.contains(element) || !(ours.contains(element) || theirs.contains(element)) {
+        return true;
+    }
+    let restored = ours.contains(element);
+    conflicts.push(format!(
+        "`{}` mentions {} `{}`, which {} removes",
+        entry,
+        kind,
+        ty(name),
+        if restored { "theirs" } else { "ours" }
+    ));
+    if restored {
+        merged.push(element.clone());
+    }
+    restored
+}
+
+fn rename_states(flow: &mut RawFlow, renames: &BTreeMap<String, String>) {
+    let rename = |s: &mut RawState| {
+        if let Some(to) = renames.get(&s.0) {
+            s.0 = to.clone();
+        }
+    };
+    flow.states.iter_mut().for_each(rename);
+    for r in &mut flow.references {
+        rename(&mut r.state);
+    }
+    for t in &mut flow.transitions {
+        rename(&mut t.from);
+        rename(&mut t.to);
+    }
+    for o in &mut flow.overlays {
+        rename(&mut o.back);
+        rename(&mut o.front);
+    }
+    if let Some(layout) = &mut flow.layout {
+        *layout = std::mem::take(layout)
+            .into_iter()
+            .map(|(name, node)| (renames.get(&name).cloned().unwrap_or(name), node))
+            .collect();
+    }
+}
+
+// Which renames to apply to the base, ours and theirs, so that all three use the merged
+// names. A rename that clashes with the other side's rename or removal is a conflict.
+fn renames(
+    [ours, theirs]: [&[Rename]; 2],
+    [ours_removed, theirs_removed]: [&[String]; 2],
+    conflicts: &mut Vec<String>,
+) -> [BTreeMap<String, String>; 3] {
+    let [mut base, mut to_ours, mut to_theirs] = [(); 3].map(|_| BTreeMap::new());
+    for Rename { from, to } in ours {
+        match theirs.iter().find(|r| r.from == *from) {
+            Some(r) if r.to != *to => {
+                conflicts.push(format!(
+                    "state `{}` is renamed to `{}` by ours and to `{}` by theirs",
+                    ty(from),
+                    ty(to),
+                    ty(&r.to)
+                ));
+                to_theirs.insert(r.to.clone(), to.clone());
+            }
+            Some(_) => {}
+            None if theirs_removed.contains(from) => {
+                conflicts.push(format!(
+                    "state `{}` is renamed to `{}` by ours and removed by theirs",
+                    ty(from),
+                    ty(to)
+                ));
+                continue;
+            }
+            None => {
+                to_theirs.insert(from.clone(), to.clone());
+            }
+        }
+        base.insert(from.clone(), to.clone());
+    }
+    for Rename { from, to } in theirs {
+        if ours.iter().any(|r| r.from == *from) {
+            continue;
+        }
+        if ours_removed.contains(from) {
+            conflicts.push(format!(
+                "state `{}` is removed by ours and renamed to `{}` by theirs",
+                ty(from),
+                ty(to)
+            ));
+            continue;
+        }
+        base.insert(from.clone(), to.clone());
+        to_ours.insert(from.clone(), to.clone());
+    }
+    [base, to_ours, to_theirs]
+}
+
+// Entries that one side adds and that mention a state the other side renames. They follow
+// the rename, but the side that added them did not know about it.
+fn renamed_mentions(
+    added_by: &FlowDiff,
+    renames: &BTreeMap<String, String>,
+    renamed_by: &str,
+    conflicts: &mut Vec<String>,
+) {
+    let mut conflict = |entry: String, states: &[&RawState]| {
+        for state in states {
+            if let Some(to) = renames.get(&state.0) {
+                conflicts.push(format!(
+                    "`{}` mentions state `{}`, which {} renames to `{}`",
+                    entry,
+                    ty(&state.0),
+                    renamed_by,
+                    ty(to)
+                ));
+            }
+        }
+    };
+    for r in &added_by.references.added {
+        conflict(reference(r), &[&r.state]);
+    }
+    for t in &added_by.transitions.added {
+        conflict(transition(t), &[&t.from, &t.to]);
+    }
+    for o in &added_by.overlays.added {
+        conflict(overlay(o), &[&o.back, &o.front]);
+    }
+}
+
+pub fn merge(base: &RawFlow, ours: &RawFlow, theirs: &RawFlow) -> Merged {
+    let [mut base, mut ours, mut theirs] = [base, ours, theirs].map(canonical);
+    let (ours_diff, theirs_diff) = (diff(&base, &ours), diff(&base, &theirs));
+    let mut conflicts = Vec::new();
+
+    let [base_renames, ours_renames, theirs_renames] = renames(
+        [&ours_diff.renamed_states, &theirs_diff.renamed_states],
+        [&ours_diff.states.removed, &theirs_diff.states.removed],
+        &mut conflicts,
+    );
+    renamed_mentions(&theirs_diff, &theirs_renames, "ours", &mut conflicts);
+    renamed_mentions(&ours_diff, &ours_renames, "theirs", &mut conflicts);
+    rename_states(&mut base, &base_renames);
+    rename_states(&mut ours, &ours_renames);
+    rename_states(&mut theirs, &theirs_renames);
+
+    let c = &mut conflicts;
+    let mut resources = entries(
+        "resource",
+        [&base.resources, &ours.resources, &theirs.resources],
+        |r| r.clone(),
+        |r| ty(&r.0),
+        c,
+    );
+    let mut states = entries(
+        "state",
+        [&base.states, &ours.states, &theirs.states],
+        |s| s.clone(),
+        |s| ty(&s.0),
+        c,
+    );
+    let mut intermediates = entries(
+        "intermediate",
+        [
+            &base.intermediates,
+            &ours.intermediates,
+            &theirs.intermediates,
+        ],
+        |i| i.clone(),
+        |i| ty(&i.0),
+        c,
+    );
+    let mut references = entries(
+        "reference",
+        [&base.references, &ours.references, &theirs.references],
+        |r| (r.state.clone(), r.resource.clone()),
+        reference,
+        c,
+    );
+    let mut transitions = entries(
+        "transition",
+        [&base.transitions, &ours.transitions, &theirs.transitions],
+        |t| (t.from.clone(), t.intermediate.clone(), t.to.clone()),
+        transition,
+        c,
+    );
+    let mut overlays = entries(
+        "overlay",
+        [&base.overlays, &ours.overlays, &theirs.overlays],
+        |o| o.clone(),
+        overlay,
+        c,
+    );
+    // Positions are not worth a conflict; ours wins silently.
+    let layouts = [&base, &ours, &theirs].map(|f| {
+        let layout = f.layout.clone().unwrap_or_default();
+        layout.into_iter().collect::<Vec<_>>()
+    });
+    let layout = entries(
+        "layout",
+        [&layouts[0], &layouts[1], &layouts[2]],
+        |(name, _)| name.clone(),
+        |(name, _)| name.clone(),
+        &mut Vec::new(),
+    );
+
+    let c = &mut conflicts;
+    let all_states = [&ours.states[..], &theirs.states[..]];
+    references.retain(|r| {
+        let entry = reference(r);
+        let sides = [&ours.resources[..], &theirs.resources[..]];
+        let state = ("state", r.state.0.as_str());
+        let resource = ("resource", r.resource.0.as_str());
+        survives(&r.state, state, &entry, &mut states, all_states, c)
+            && survives(&r.resource, resource, &entry, &mut resources, sides, c)
+    });
+    transitions.retain(|t| {
+        let entry = transition(t);
+        let sides = [&ours.intermediates[..], &theirs.intermediates[..]];
+        let (from, to) = (("state", t.from.0.as_str()), ("state", t.to.0.as_str()));
+        survives(&t.from, from, &entry, &mut states, all_states, c)
+            && survives(&t.to, to, &entry, &mut states, all_states, c)
+            && t.intermediate.as_ref().is_none_or(|i| {
+                let intermediate = ("intermediate", i.0.as_str());
+                survives(i, intermediate, &entry, &mut intermediates, sides, c)
+            })
+    });
+    overlays.retain(|o| {
+        let entry = overlay(o);
+        let (back, front) = (("state", o.back.0.as_str()), ("state", o.front.0.as_str()));
+        survives(&o.back, back, &entry, &mut states, all_states, c)
+            && survives(&o.front, front, &entry, &mut states, all_states, c)
+    });
+
+    let layout: BTreeMap<_, _> = layout.into_iter().collect();
+    let layout = match layout.is_empty() && ours.layout.is_none() {
+        true => None,
+        false => Some(layout),
+    };
+    Merged {
+        flow: RawFlow {
+            resources,
+            states,
+            intermediates,
+            references,
+            transitions,
+            overlays,
+            layout,
+        },
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod merge_test {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::arbitrary::raw_flow;
+    use crate::Flow;
+
+    const BASE: &str = "resource: [Save] state: [Title, Game, Pause, GameOver] \
+        intermediate: [Start] reference: [Game -< mut Save, Pause -< Save] \
+        transition: [Title >- Start -> Game, Game >--> GameOver, GameOver >--> Title] \
+        overlay: [Game ^ Pause]";
+
+    fn merged(ours: &str, theirs: &str) -> (String, Vec<String>) {
+        let flow = |text| RawFlow::from(Flow::from_str(text).unwrap());
+        let merged = merge(&flow(BASE), &flow(ours), &flow(theirs));
+        (merged.flow.to_string(), merged.conflicts)
+    }
+
+    #[test]
+    fn independent_changes_merge() {
+        let ours = BASE.replace("GameOver]", "GameOver, Menu]").replace(
+            "GameOver >--> Title]",
+            "GameOver >--> Title, Title >--> Menu]",
+        );
+        let theirs = BASE
+            .replace("Pause -< Save", "Pause -< mut Save")
+            .replace("Game,", "Play,")
+            .replace("Game ", "Play ");
+        let (text, conflicts) = merged(&ours, &theirs);
+        assert!(conflicts.is_empty(), "{:?}", conflicts);
+        assert_eq!(
+            text,
+            "resource: [Save]\n\
+            state: [Title, Play, Pause, GameOver, Menu]\n\
+            intermediate: [Start]\n\
+            reference: [Play -< mut Save, Pause -< mut Save]\n\
+            transition: [\n    \
+                Title >- Start -> Play,\n    \
+                Play >--> GameOver,\n    \
+                GameOver >--> Title,\n    \
+                Title >--> Menu,\n\
+            ]\n\
+            overlay: [Play ^ Pause]\n"
+        );
+    }
+
+    #[test]
+    fn the_same_change_on_both_sides_is_not_a_conflict() {
+        let both = BASE.replace(", Pause -< Save", "");
+        let (text, conflicts) = merged(&both, &both);
+        assert!(conflicts.is_empty());
+        assert!(text.contains("reference: [Game -< mut Save]\n"));
+    }
+
+    #[test]
+    fn mutability_against_removal_conflicts() {
+        let ours = BASE.replace("Pause -< Save", "Pause -< mut Save");
+        let theirs = BASE.replace(", Pause -< Save", "");
+        let (text, conflicts) = merged(&ours, &theirs);
+        assert_eq!(
+            conflicts,
+            ["reference `Pause -< Save` is changed to `Pause -< mut Save` by ours and removed by theirs"]
+        );
+        assert!(text.contains("reference: [Game -< mut Save, Pause -< mut Save]\n"));
+    }
+
+    #[test]
+    fn removed_elements_against_new_entries_conflict() {
+        let adds = BASE.replace("Title]", "Title, Pause >--> Title]");
+        let removes = BASE
+            .replace(", Pause,", ",")
+            .replace(", Pause -< Save", "")
+            .replace("Game ^ Pause", "");
+
+        let (text, conflicts) = merged(&adds, &removes);
+        assert_eq!(
+            conflicts,
+            ["`Pause >--> Title` mentions state `Pause`, which theirs removes"]
+        );
+        assert!(text.contains("state: [Title, Game, GameOver, Pause]\n"));
+        assert!(text.contains("Pause >--> Title"));
+
+        let (text, conflicts) = merged(&removes, &adds);
+        assert_eq!(
+            conflicts,
+            ["`Pause >--> Title` mentions state `Pause`, which ours removes"]
+        );
+        assert!(!text.contains("Pause"));
+    }
+
+    #[test]
+    fn conflicting_renames_keep_ours() {
+        let rename = |to: &str| {
+            BASE.replace("Game,", &format!("{},", to))
+                .replace("Game ", &format!("{} ", to))
+        };
+        let (text, conflicts) = merged(&rename("Play"), &rename("Level"));
+        assert_eq!(
+            conflicts,
+            ["state `Game` is renamed to `Play` by ours and to `Level` by theirs"]
+        );
+        assert!(text.contains("state: [Title, Play, Pause, GameOver]\n"));
+        assert!(!text.contains("Level"));
+    }
+
+    #[test]
+    fn new_entries_against_renames_conflict() {
+        let renames = BASE.replace("GameOver", "Results");
+        let adds = BASE.replace("Title]", "Title, GameOver >--> Game]");
+        let (text, conflicts) = merged(&renames, &adds);
+        assert_eq!(
+            conflicts,
+            ["`GameOver >--> Game` mentions state `GameOver`, which ours renames to `Results`"]
+        );
+        assert!(text.contains("Results >--> Game"));
+        assert!(!text.contains("GameOver"));
+    }
+
+    #[test]
+    fn unconnected_states_are_not_renamed() {
+        let ours = BASE.replace("GameOver]", "GameOver, Bar]");
+        let base = BASE.replace("GameOver]", "GameOver, Foo]");
+        let theirs = base.replace("Title]", "Title, Foo >--> Title]");
+        let flow = |text: &str| RawFlow::from(Flow::from_str(text).unwrap());
+        let merged = merge(&flow(&base), &flow(&ours), &flow(&theirs));
+        assert_eq!(
+            merged.conflicts,
+            ["`Foo >--> Title` mentions state `Foo`, which ours removes"]
+        );
+        let text = merged.flow.to_string();
+        assert!(text.contains("state: [Title, Game, Pause, GameOver, Bar]\n"));
+        assert!(!text.contains("Foo"));
+    }
+
+    proptest! {
+        #[test]
+        fn merging_an_unchanged_side_takes_the_other(base in raw_flow(), ours in raw_flow()) {
+            let merged = merge(&base, &ours, &base);
+            prop_assert!(merged.conflicts.is_empty(), "{:?}", merged.conflicts);
+            prop_assert_eq!(merged.flow, canonical(&ours));
+        }
+    }
+}
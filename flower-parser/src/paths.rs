@@ -0,0 +1,221 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::interpreter::Step;
+use super::raw_ast::{RawFlow, RawTransition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    Transitions,
+    // Every two transitions where the first ends in the state the second starts from.
+    AdjacentPairs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestPath {
+    pub start: String,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathSet {
+    pub paths: Vec<TestPath>,
+    // Requirements that can not be reached from the initial state.
+    pub unreachable: Vec<Vec<Step>>,
+}
+
+fn step(t: &RawTransition) -> Step {
+    Step::Transition {
+        from: t.from.0.clone(),
+        intermediate: t.intermediate.as_ref().map(|i| i.0.clone()),
+        to: t.to.0.clone(),
+        after_ms: t.after_ms,
+    }
+}
+
+fn requirements(flow: &RawFlow, coverage: Coverage) -> Vec<Vec<usize>> {
+    let transitions = &flow.transitions;
+    match coverage {
+        Coverage::Transitions => (0..transitions.len()).map(|i| vec![i]).collect(),
+        Coverage::AdjacentPairs => (0..transitions.len())
+            .flat_map(|i| {
+                (0..transitions.len())
+                    .filter(move |&j| transitions[i].to == transitions[j].from)
+                    .map(move |j| vec![i, j])
+            })
+            .collect(),
+    }
+}
+
+// Shortest transition sequences from `from` to every reachable state.
+fn shortest_paths<'a>(flow: &'a RawFlow, from: &'a str) -> HashMap<&'a str, Vec<usize>> {
+    let mut paths = HashMap::from([(from, Vec::new())]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(state) = queue.pop_front() {
+        for (index, t) in flow.transitions.iter().enumerate() {
+            if t.from.0 == state && !paths.contains_key(t.to.0.as_str()) {
+                let mut path = paths[state].clone();
+                path.push(index);
+                paths.insert(&t.to.0, path);
+                queue.push_back(&t.to.0);
+            }
+        }
+    }
+    paths
+}
+
+fn covers(path: &[usize], requirement: &[usize]) -> bool {
+    path.windows(requirement.len()).any(|w| w == requirement)
+}
+
+// Greedily extends a path from `initial` towards the nearest uncovered requirement,
+// starting a new path whenever the current one runs into a dead end.
+pub fn covering_paths(flow: &RawFlow, initial: &str, coverage: Coverage) -> PathSet {
+    let requirements = requirements(flow, coverage);
+    let mut uncovered: Vec<&Vec<usize>> = requirements.iter().collect();
+    let mut paths = Vec::new();
+
+    loop {
+        let mut path: Vec<usize> = Vec::new();
+        loop {
+            let here = path
+                .last()
+                .map_or(initial, |&i| flow.transitions[i].to.0.as_str());
+            let reachable = shortest_paths(flow, here);
+            let next = uncovered
+                .iter()
+                .filter_map(|r| {
+                    let start = flow.transitions[r[0]].from.0.as_str();
+                    reachable.get(start).map(|prefix| (prefix, *r))
+                })
+                .min_by_key(|(prefix, _)| prefix.len());
+            let (prefix, requirement) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            path.extend(prefix);
+            path.extend(requirement);
+            uncovered.retain(|r| !covers(&path, r));
+        }
+        if path.is_empty() {
+            break;
+        }
+        paths.push(TestPath {
+            start: initial.to_string(),
+            steps: path.iter().map(|&i| step(&flow.transitions[i])).collect(),
+        });
+    }
+
+    let unreachable = uncovered
+        .iter()
+        .map(|r| r.iter().map(|&i| step(&flow.transitions[i])).collect())
+        .collect();
+    PathSet { paths, unreachable }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars().filter(|c| c.is_alphanumeric() || *c == '_') {
+        if c.is_uppercase() && !snake.is_empty() && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+impl PathSet {
+    pub fn to_rust_tests(&self) -> String {
+        let mut out = String::new();
+        for (index, path) in self.paths.iter().enumerate() {
+            let end = match path.steps.last() {
+                Some(Step::Transition { to, .. }) => to.as_str(),
+                _ => path.start.as_str(),
+            };
+            out.push_str(&format!(
+                "#[test]\nfn path_{}_{}_to_{}() {{\n",
+                index + 1,
+                snake_case(&path.start),
+                snake_case(end)
+            ));
+            for step in &path.steps {
+                out.push_str(&format!("    // {}\n", step));
+            }
+            out.push_str("    todo!()\n}\n");
+            if index + 1 < self.paths.len() {
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod paths_test {
+    use std::str::FromStr;
+
+    use super::{covering_paths, Coverage};
+    use crate::{Flow, RawFlow};
+
+    const FLOW_STR: &str = r#"resource: []
+state: [Title, Game, GameOver, Credits, Orphan]
+intermediate: [Start]
+reference: []
+transition: [
+    Title >- Start -> Game,
+    Game >--> GameOver,
+    Game >--> Title,
+    GameOver >--> Title,
+    Title >--> Credits,
+    Orphan >--> Title,
+]
+overlay: []
+"#;
+
+    fn raw_flow() -> RawFlow {
+        Flow::from_str(FLOW_STR).unwrap().into()
+    }
+
+    #[test]
+    fn every_transition() {
+        let paths = covering_paths(&raw_flow(), "Title", Coverage::Transitions);
+        assert_eq!(
+            paths.to_rust_tests(),
+            r#"#[test]
+fn path_1_title_to_credits() {
+    // Title >- Start -> Game
+    // Game >--> GameOver
+    // GameOver >--> Title
+    // Title >--> Credits
+    todo!()
+}
+
+#[test]
+fn path_2_title_to_title() {
+    // Title >- Start -> Game
+    // Game >--> Title
+    todo!()
+}
+"#
+        );
+        assert_eq!(paths.unreachable.len(), 1);
+    }
+
+    #[test]
+    fn adjacent_pairs() {
+        let flow = raw_flow();
+        let paths = covering_paths(&flow, "Title", Coverage::AdjacentPairs);
+        for pair in [
+            ("Game >--> Title", "Title >--> Credits"),
+            ("GameOver >--> Title", "Title >- Start -> Game"),
+        ] {
+            assert!(paths.paths.iter().any(|p| p
+                .steps
+                .windows(2)
+                .any(|w| (w[0].to_string().as_str(), w[1].to_string().as_str()) == pair)));
+        }
+        // `Orphan >--> Title` followed by anything out of `Title`.
+        assert_eq!(paths.unreachable.len(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use super::raw_ast::{RawFlow, RawLayoutNode, RawOverlay, RawReference, RawTransition};
+
+// Items whose entries fit within this width stay on one line.
+const WIDTH: usize = 80;
+
+// Raw names are token strings such as `game :: Level < 1 >`; prints them as `game::Level<1>`.
+pub(crate) fn ty(name: &str) -> String {
+    name.replace(" :: ", "::")
+        .replace(":: ", "::")
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+}
+
+pub(crate) fn reference(r: &RawReference) -> String {
+    let mutable = if r.mutable { "mut " } else { "" };
+    format!("{} -< {}{}", ty(&r.state.0), mutable, ty(&r.resource.0))
+}
+
+pub(crate) fn transition(t: &RawTransition) -> String {
+    let (from, to) = (ty(&t.from.0), ty(&t.to.0));
+    let mut s = match &t.intermediate {
+        Some(intermediate) => format!("{} >- {} -> {}", from, ty(&intermediate.0), to),
+        None => format!("{} >--> {}", from, to),
+    };
+    match t.after_ms {
+        Some(ms) if ms % 1000 == 0 => s += &format!(" after {}s", ms / 1000),
+        Some(ms) => s += &format!(" after {}ms", ms),
+        None => {}
+    }
+    s
+}
+
+pub(crate) fn overlay(o: &RawOverlay) -> String {
+    format!("{} ^ {}", ty(&o.back.0), ty(&o.front.0))
+}
+
+fn layout_node(name: &str, node: &RawLayoutNode) -> String {
+    let mut fields = vec![format!("x: {}", node.x), format!("y: {}", node.y)];
+    if let Some(colour) = &node.colour {
+        fields.push(format!("colour: {:?}", colour));
+    }
+    if node.collapsed {
+        fields.push("collapsed: true".to_string());
+    }
+    format!("{} {{ {} }}", ty(name), fields.join(", "))
+}
+
+// Prints `open`, the entries and `close` on one line if they fit, else one entry per line.
+fn block(f: &mut fmt::Formatter<'_>, open: &str, close: &str, entries: &[String]) -> fmt::Result {
+    let inline = format!("{}{}{}", open, entries.join(", "), close);
+    if inline.len() <= WIDTH {
+        return writeln!(f, "{}", inline);
+    }
+    writeln!(f, "{}", open)?;
+    for entry in entries {
+        writeln!(f, "    {},", entry)?;
+    }
+    writeln!(f, "{}", close)
+}
+
+fn item(f: &mut fmt::Formatter<'_>, keyword: &str, entries: &[String]) -> fmt::Result {
+    block(f, &format!("{}: [", keyword), "]", entries)
+}
+
+// The canonical text form of a flow, with items in declaration order after the layout.
+impl fmt::Display for RawFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(nodes) = &self.layout {
+            // Sorted as printed, since `a::B` and `a :: B` sort differently as raw names.
+            let mut entries: Vec<_> = nodes
+                .iter()
+                .map(|(name, node)| (ty(name), layout_node(name, node)))
+                .collect();
+            entries.sort();
+            let entries: Vec<_> = entries.into_iter().map(|(_, entry)| entry).collect();
+            block(f, "#[layout(", ")]", &entries)?;
+        }
+        let resources: Vec<_> = self.resources.iter().map(|r| ty(&r.0)).collect();
+        let states: Vec<_> = self.states.iter().map(|s| ty(&s.0)).collect();
+        let intermediates: Vec<_> = self.intermediates.iter().map(|i| ty(&i.0)).collect();
+        item(f, "resource", &resources)?;
+        item(f, "state", &states)?;
+        item(f, "intermediate", &intermediates)?;
+        item(
+            f,
+            "reference",
+            &self.references.iter().map(reference).collect::<Vec<_>>(),
+        )?;
+        item(
+            f,
+            "transition",
+            &self.transitions.iter().map(transition).collect::<Vec<_>>(),
+        )?;
+        item(
+            f,
+            "overlay",
+            &self.overlays.iter().map(overlay).collect::<Vec<_>>(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod pretty_test {
+    use std::str::FromStr;
+
+    use crate::{Flow, RawFlow};
+
+    #[test]
+    fn pretty_round_trips() {
+        let flow: RawFlow = Flow::from_str(
+            "state: [A, B] resource: [] intermediate: [N] reference: [] \
+             transition: [A >- N -> B after 1.5s, B >--> A after 2s] overlay: [A ^ B]",
+        )
+        .unwrap()
+        .into();
+        let pretty = flow.to_string();
+        assert_eq!(
+            pretty,
+            "resource: []\nstate: [A, B]\nintermediate: [N]\nreference: []\n\
+             transition: [A >- N -> B after 1500ms, B >--> A after 2s]\noverlay: [A ^ B]\n"
+        );
+        assert_eq!(RawFlow::from(Flow::from_str(&pretty).unwrap()), flow);
+    }
+
+    #[test]
+    fn layout_comes_first() {
+        let text = "#[layout(Title { x: 0, y: -40, colour: \"#f392a3\" }, \
+                    game::Level<1> { x: 120, y: 0, collapsed: true })] \
+                    resource: [] state: [Title, game::Level<1>] intermediate: [] \
+                    reference: [] transition: [] overlay: []";
+        let flow: RawFlow = Flow::from_str(text).unwrap().into();
+        let layout = flow.layout.as_ref().unwrap();
+        assert_eq!(layout["Title"].y, -40);
+        assert!(layout["game :: Level < 1 >"].collapsed);
+        assert_eq!(
+            flow.to_string(),
+            "#[layout(\n    Title { x: 0, y: -40, colour: \"#f392a3\" },\n    \
+             game::Level<1> { x: 120, y: 0, collapsed: true },\n)]\n\
+             resource: []\nstate: [Title, game::Level<1>]\nintermediate: []\n\
+             reference: []\ntransition: []\noverlay: []\n"
+        );
+        assert_eq!(
+            RawFlow::from(Flow::from_str(&flow.to_string()).unwrap()),
+            flow
+        );
+    }
+}
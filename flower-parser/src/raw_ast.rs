@@ -6,13 +6,15 @@ use std::str::FromStr;
 use syn::{
     parse2,
     punctuated::{Pair, Punctuated},
-    token::Bracket,
-    Token,
+    token::{Brace, Bracket, Paren},
+    Ident, Lit, LitBool, LitInt, LitStr, Token, TypePath,
 };
 
 use serde::{Deserialize, Serialize};
 
 use super::ast::*;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::result;
 
 #[derive(Debug)]
@@ -22,7 +24,25 @@ pub enum Error {
 }
 pub type Result<T> = result::Result<T, Error>;
 
-#[derive(Serialize, Deserialize)]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SynError(e) => write!(f, "{}", e),
+            Error::LexError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+fn parse_type_path(s: &str) -> Result<TypePath> {
+    let input = TokenStream::from_str(s).map_err(Error::LexError)?;
+    check_tokens(input.clone()).map_err(Error::SynError)?;
+    parse2(input).map_err(Error::SynError)
+}
+
+/// A resource type, written as a Rust type path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS, schemars::JsonSchema), ts(rename = "Resource"))]
+#[cfg_attr(test, schemars(rename = "Resource"))]
 pub struct RawResource(pub String);
 impl From<Resource> for RawResource {
     fn from(r: Resource) -> Self {
@@ -33,13 +53,15 @@ impl From<Resource> for RawResource {
 impl TryFrom<RawResource> for Resource {
     type Error = Error;
     fn try_from(rr: RawResource) -> Result<Self> {
-        let input = TokenStream::from_str(&rr.0).map_err(Error::LexError)?;
-        let ty = parse2(input).map_err(Error::SynError)?;
+        let ty = parse_type_path(&rr.0)?;
         Ok(Resource(ty))
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A state type, written as a Rust type path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS, schemars::JsonSchema), ts(rename = "State"))]
+#[cfg_attr(test, schemars(rename = "State"))]
 pub struct RawState(pub String);
 impl From<State> for RawState {
     fn from(state: State) -> Self {
@@ -50,15 +72,22 @@ impl From<State> for RawState {
 impl TryFrom<RawState> for State {
     type Error = Error;
     fn try_from(rs: RawState) -> Result<Self> {
-        let input = TokenStream::from_str(&rs.0).map_err(Error::LexError)?;
-        let ty = parse2(input).map_err(Error::SynError)?;
+        let ty = parse_type_path(&rs.0)?;
         Ok(State(ty))
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A state borrowing a resource while it is on the stack.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    test,
+    derive(ts_rs::TS, schemars::JsonSchema),
+    ts(rename = "Reference")
+)]
+#[cfg_attr(test, schemars(rename = "Reference", deny_unknown_fields))]
 pub struct RawReference {
     pub state: RawState,
+    /// Whether the state borrows the resource mutably.
     pub mutable: bool,
     pub resource: RawResource,
 }
@@ -94,7 +123,14 @@ impl TryFrom<RawReference> for Reference {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// An intermediate type that a transition passes through, written as a Rust type path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    test,
+    derive(ts_rs::TS, schemars::JsonSchema),
+    ts(rename = "Intermediate")
+)]
+#[cfg_attr(test, schemars(rename = "Intermediate"))]
 pub struct RawIntermediate(pub String);
 impl From<Intermediate> for RawIntermediate {
     fn from(intermediate: Intermediate) -> Self {
@@ -105,27 +141,39 @@ impl From<Intermediate> for RawIntermediate {
 impl TryFrom<RawIntermediate> for Intermediate {
     type Error = Error;
     fn try_from(ri: RawIntermediate) -> Result<Self> {
-        let input = TokenStream::from_str(&ri.0).map_err(Error::LexError)?;
-        let ty = parse2(input).map_err(Error::SynError)?;
+        let ty = parse_type_path(&ri.0)?;
         Ok(Intermediate(ty))
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A transition replacing `from` with `to`, optionally through an intermediate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    test,
+    derive(ts_rs::TS, schemars::JsonSchema),
+    ts(rename = "Transition")
+)]
+#[cfg_attr(test, schemars(rename = "Transition", deny_unknown_fields))]
 pub struct RawTransition {
     pub from: RawState,
     pub intermediate: Option<RawIntermediate>,
     pub to: RawState,
+    /// Makes the transition timed: it fires on its own after this many milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional, type = "number"))]
+    pub after_ms: Option<u64>,
 }
 impl From<Transition> for RawTransition {
     fn from(transition: Transition) -> Self {
         let from = transition.from.into();
         let intermediate = transition.intermediate.map(|i| i.into());
         let to = transition.to.into();
+        let after_ms = transition.delay.map(|d| d.millis);
         RawTransition {
             from,
             intermediate,
             to,
+            after_ms,
         }
     }
 }
@@ -137,6 +185,11 @@ impl TryFrom<RawTransition> for Transition {
         let intermediate = rt.intermediate.map(|v| v.try_into()).transpose()?;
         let rarrow_token = Token![->](Span::call_site());
         let to = rt.to.try_into()?;
+        let delay = rt.after_ms.map(|millis| Delay {
+            after_token: kw::after(Span::call_site()),
+            lit: Lit::Int(LitInt::new(&format!("{}ms", millis), Span::call_site())),
+            millis,
+        });
 
         Ok(Transition {
             from,
@@ -144,11 +197,15 @@ impl TryFrom<RawTransition> for Transition {
             intermediate,
             rarrow_token,
             to,
+            delay,
         })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A state `front` that can be pushed on top of `back`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS, schemars::JsonSchema), ts(rename = "Overlay"))]
+#[cfg_attr(test, schemars(rename = "Overlay", deny_unknown_fields))]
 pub struct RawOverlay {
     pub back: RawState,
     pub front: RawState,
@@ -174,7 +231,66 @@ impl TryFrom<RawOverlay> for Overlay {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// How the editor draws an element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    test,
+    derive(ts_rs::TS, schemars::JsonSchema),
+    ts(rename = "LayoutNode")
+)]
+#[cfg_attr(test, schemars(rename = "LayoutNode", deny_unknown_fields))]
+pub struct RawLayoutNode {
+    pub x: i32,
+    pub y: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub colour: Option<String>,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+impl From<LayoutNode> for RawLayoutNode {
+    fn from(node: LayoutNode) -> Self {
+        RawLayoutNode {
+            x: node.x,
+            y: node.y,
+            colour: node.colour,
+            collapsed: node.collapsed,
+        }
+    }
+}
+impl TryFrom<(String, RawLayoutNode)> for LayoutNode {
+    type Error = Error;
+    fn try_from((name, node): (String, RawLayoutNode)) -> Result<Self> {
+        let span = Span::call_site();
+        let field = |name: &str, value: Lit| LayoutField {
+            name: Ident::new(name, span),
+            colon_token: Default::default(),
+            value,
+        };
+        let mut fields = Punctuated::new();
+        fields.push(field("x", Lit::Int(LitInt::new(&node.x.to_string(), span))));
+        fields.push(field("y", Lit::Int(LitInt::new(&node.y.to_string(), span))));
+        if let Some(colour) = &node.colour {
+            fields.push(field("colour", Lit::Str(LitStr::new(colour, span))));
+        }
+        if node.collapsed {
+            fields.push(field("collapsed", Lit::Bool(LitBool::new(true, span))));
+        }
+        Ok(LayoutNode {
+            element: parse_type_path(&name)?,
+            brace: Brace::default(),
+            fields,
+            x: node.x,
+            y: node.y,
+            colour: node.colour,
+            collapsed: node.collapsed,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS, schemars::JsonSchema), ts(rename = "FlowBody"))]
+#[cfg_attr(test, schemars(rename = "FlowBody", deny_unknown_fields))]
 pub struct RawFlow {
     pub resources: Vec<RawResource>,
     pub states: Vec<RawState>,
@@ -182,10 +298,15 @@ pub struct RawFlow {
     pub references: Vec<RawReference>,
     pub transitions: Vec<RawTransition>,
     pub overlays: Vec<RawOverlay>,
+    /// Where the editor draws each resource, state and intermediate, keyed by name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub layout: Option<BTreeMap<String, RawLayoutNode>>,
 }
 impl From<Flow> for RawFlow {
     fn from(flow: Flow) -> Self {
         let Flow {
+            layout,
             resources,
             states,
             intermediates,
@@ -199,6 +320,16 @@ impl From<Flow> for RawFlow {
         let references = references.punct.into_iter().map(|r| r.into()).collect();
         let transitions = transitions.punct.into_iter().map(|r| r.into()).collect();
         let overlays = overlays.punct.into_iter().map(|r| r.into()).collect();
+        let layout = layout.map(|layout| {
+            layout
+                .punct
+                .into_iter()
+                .map(|node| {
+                    let name = node.element.to_token_stream().to_string();
+                    (name, node.into())
+                })
+                .collect()
+        });
         RawFlow {
             resources,
             states,
@@ -206,6 +337,7 @@ impl From<Flow> for RawFlow {
             references,
             transitions,
             overlays,
+            layout,
         }
     }
 }
@@ -239,8 +371,22 @@ impl TryFrom<RawFlow> for Flow {
         let references = v2i(kw::reference::default(), rf.references)?;
         let transitions = v2i(kw::transition::default(), rf.transitions)?;
         let overlays = v2i(kw::overlay::default(), rf.overlays)?;
+        let layout = match rf.layout {
+            Some(nodes) => Some(Layout {
+                pound_token: Default::default(),
+                bracket: Bracket::default(),
+                keyword: kw::layout::default(),
+                paren: Paren::default(),
+                punct: nodes
+                    .into_iter()
+                    .map(LayoutNode::try_from)
+                    .collect::<Result<_>>()?,
+            }),
+            None => None,
+        };
 
         Ok(Flow {
+            layout,
             resources,
             states,
             intermediates,
@@ -257,7 +403,7 @@ mod raw_ast_test {
 
     use crate::Transition;
 
-    use super::{Flow, Reference};
+    use super::{Flow, RawFlow, RawState, Reference};
     use proc_macro2::TokenStream;
     use syn::parse2;
 
@@ -306,4 +452,77 @@ intermediate: [N]
         let transition: Transition = parse2(input).unwrap();
         log::debug!("{:?}", transition);
     }
+
+    const TRANSITION_AFTER_STR: &str = r#"Splash >--> Title after 1.5s"#;
+    #[test]
+    fn transition_after_from_str() {
+        let _ = env_logger::try_init();
+        let input = TokenStream::from_str(TRANSITION_AFTER_STR).unwrap();
+        log::debug!("{}", input);
+        let transition: Transition = parse2(input).unwrap();
+        log::debug!("{:?}", transition);
+        assert_eq!(transition.delay.unwrap().millis, 1500);
+    }
+
+    fn nested(open: &str, close: &str, depth: usize) -> String {
+        format!(
+            "resource: [] state: [{}A{}] intermediate: [] reference: [] transition: [] overlay: []",
+            open.repeat(depth),
+            close.repeat(depth)
+        )
+    }
+
+    // Found by the `parse_flow` fuzz target: deep nesting overflowed the stack.
+    #[test]
+    fn deeply_nested_input_is_rejected() {
+        assert!(Flow::from_str(&nested("A<", ">", 16)).is_ok());
+        for (open, close) in [("A<", ">"), ("A<fn() -> ", ">"), ("A<(", ")>"), ("[", "]")] {
+            assert!(Flow::from_str(&nested(open, close, 100_000)).is_err());
+        }
+
+        let state = format!("{}A{}", "A<".repeat(100_000), ">".repeat(100_000));
+        let raw = RawFlow {
+            resources: vec![],
+            states: vec![RawState(state)],
+            intermediates: vec![],
+            references: vec![],
+            transitions: vec![],
+            overlays: vec![],
+            layout: None,
+        };
+        assert!(Flow::try_from(raw).is_err());
+    }
+
+    // Found by the `parse_flow` fuzz target: syn panicked on literals it does not recognize.
+    #[test]
+    fn unsupported_literals_are_rejected() {
+        for literal in ["(/*ERROR*/)", "c\"save\""] {
+            let input = FLOW_STR.replace("[A]", &format!("[A<{}>]", literal));
+            assert!(Flow::from_str(&input).is_err(), "{}", literal);
+        }
+        let input = FLOW_STR.replace("[A]", "[A<\"save\", b'a', 1.5, -1>]");
+        assert!(Flow::from_str(&input).is_ok());
+    }
+
+    #[test]
+    fn layout_fields_are_checked() {
+        let layout =
+            |fields: &str| FLOW_STR.to_string() + &format!("#[layout(S {{ {} }})]", fields);
+        for (fields, error) in [
+            ("x: 0", "`y` is required"),
+            ("x: 0, y: 0, x: 1", "`x` is given more than once"),
+            ("x: 0, y: \"0\"", "expected an integer"),
+            ("x: 0, y: 0, colour: red", "expected literal"),
+            (
+                "x: 0, y: 0, hidden: true",
+                "expected `x`, `y`, `colour` or `collapsed`",
+            ),
+        ] {
+            let input = format!("#[layout(S {{ {} }})]\n{}", fields, FLOW_STR);
+            let e = Flow::from_str(&input).unwrap_err();
+            assert_eq!(e.to_string(), error, "{}", fields);
+        }
+        // Only before the items.
+        assert!(Flow::from_str(&layout("x: 0, y: 0")).is_err());
+    }
 }
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use proc_macro2::{LexError, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{parse2, TypePath};
+
+use super::ast::{check_tokens, Flow, ParseError};
+use super::diagnostic::Position;
+use super::pretty::ty;
+
+#[derive(Debug)]
+pub enum Error {
+    Name(ParseError),
+    Exists(String),
+    Io(PathBuf, io::Error),
+    Lex(PathBuf, LexError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Name(e) => write!(f, "not a type path: {}", e),
+            Error::Exists(name) => write!(f, "`{}` already appears in the flow", name),
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Lex(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn type_path(s: &str) -> Result<TypePath, Error> {
+    let tokens = TokenStream::from_str(s).map_err(|e| Error::Name(ParseError::LexError(e)))?;
+    check_tokens(tokens.clone()).map_err(|e| Error::Name(ParseError::SynError(e)))?;
+    parse2(tokens).map_err(|e| Error::Name(ParseError::SynError(e)))
+}
+
+// Every type path in the flow, in the order they are written.
+fn type_paths(flow: &mut Flow) -> Vec<&mut TypePath> {
+    let mut paths = Vec::new();
+    if let Some(layout) = &mut flow.layout {
+        paths.extend(layout.punct.iter_mut().map(|n| &mut n.element));
+    }
+    paths.extend(flow.resources.punct.iter_mut().map(|r| &mut r.0));
+    paths.extend(flow.states.punct.iter_mut().map(|s| &mut s.0));
+    paths.extend(flow.intermediates.punct.iter_mut().map(|i| &mut i.0));
+    for r in flow.references.punct.iter_mut() {
+        paths.extend([&mut r.state.0, &mut r.resource.0]);
+    }
+    for t in flow.transitions.punct.iter_mut() {
+        paths.push(&mut t.from.0);
+        paths.extend(t.intermediate.as_mut().map(|i| &mut i.0));
+        paths.push(&mut t.to.0);
+    }
+    for o in flow.overlays.punct.iter_mut() {
+        paths.extend([&mut o.back.0, &mut o.front.0]);
+    }
+    paths
+}
+
+// Replaces every occurrence of the type path `from` with `to`, in every section and the
+// layout, and returns how many were replaced. Paths match by tokens, so `a::B` matches
+// `a :: B`. Renaming onto a name the flow already uses would merge two elements, so it is
+// refused.
+pub fn rename(flow: &mut Flow, from: &str, to: &str) -> Result<usize, Error> {
+    let from = type_path(from)?.to_token_stream().to_string();
+    let to = type_path(to)?;
+    let to_name = to.to_token_stream().to_string();
+    let paths = type_paths(flow);
+    if to_name != from
+        && paths
+            .iter()
+            .any(|p| p.to_token_stream().to_string() == to_name)
+    {
+        return Err(Error::Exists(ty(&to_name)));
+    }
+    let mut count = 0;
+    for path in paths {
+        if path.to_token_stream().to_string() == from {
+            *path = to.clone();
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// An identifier in a Rust source file that may refer to a renamed type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mention {
+    pub path: PathBuf,
+    pub start: Position,
+    pub end: Position,
+}
+
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let io = |e| Error::Io(dir.to_path_buf(), e);
+    let mut entries = fs::read_dir(dir)
+        .map_err(io)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(io)?;
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if name != "target" && !name.starts_with('.') {
+                rust_files(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn idents(tokens: TokenStream, name: &str, path: &Path, mentions: &mut Vec<Mention>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => idents(group.stream(), name, path, mentions),
+            TokenTree::Ident(ident) if ident == name => mentions.push(Mention {
+                path: path.to_path_buf(),
+                start: ident.span().start().into(),
+                end: ident.span().end().into(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+// Finds the identifiers in the `.rs` files under `dir` that match the last segment of the
+// type path `name`, so `game::Level<1>` finds `Level`. Skips `target` and hidden
+// directories.
+pub fn rust_mentions(dir: &Path, name: &str) -> Result<Vec<Mention>, Error> {
+    let ty = type_path(name)?;
+    let ident = match ty.path.segments.last() {
+        Some(segment) => segment.ident.to_string(),
+        None => return Ok(Vec::new()),
+    };
+    let mut files = Vec::new();
+    rust_files(dir, &mut files)?;
+    let mut mentions = Vec::new();
+    for path in files {
+        let source = fs::read_to_string(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        let tokens = TokenStream::from_str(&source).map_err(|e| Error::Lex(path.clone(), e))?;
+        idents(tokens, &ident, &path, &mut mentions);
+    }
+    Ok(mentions)
+}
+
+#[cfg(test)]
+mod rename_test {
+    use super::*;
+    use crate::raw_ast::RawFlow;
+
+    const GAME: &str = "#[layout(Game { x: 200, y: 0 })] resource: [Save] \
+        state: [Title, Game, game::Game] intermediate: [] \
+        reference: [Game -< mut Save] \
+        transition: [Title >--> Game, Game >--> Title after 3s] overlay: [game::Game ^ Game]";
+
+    fn renamed(text: &str, from: &str, to: &str) -> (String, usize) {
+        let mut flow = Flow::from_str(text).unwrap();
+        let count = rename(&mut flow, from, to).unwrap();
+        (RawFlow::from(flow).to_string(), count)
+    }
+
+    #[test]
+    fn every_section_is_renamed() {
+        let (text, count) = renamed(GAME, "Game", "level :: Play<1>");
+        assert_eq!(count, 6);
+        assert_eq!(
+            text,
+            "#[layout(level::Play<1> { x: 200, y: 0 })]\n\
+            resource: [Save]\n\
+            state: [Title, level::Play<1>, game::Game]\n\
+            intermediate: []\n\
+            reference: [level::Play<1> -< mut Save]\n\
+            transition: [Title >--> level::Play<1>, level::Play<1> >--> Title after 3s]\n\
+            overlay: [game::Game ^ level::Play<1>]\n"
+        );
+    }
+
+    #[test]
+    fn paths_match_by_tokens() {
+        let (text, count) = renamed(GAME, "game :: Game", "Menu");
+        assert_eq!(count, 2);
+        assert!(text.contains("state: [Title, Game, Menu]"));
+        assert!(text.contains("overlay: [Menu ^ Game]"));
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut flow = Flow::from_str(GAME).unwrap();
+        assert!(matches!(
+            rename(&mut flow, "Game", "not a type"),
+            Err(Error::Name(_))
+        ));
+    }
+
+    #[test]
+    fn names_in_use_are_rejected() {
+        let mut flow = Flow::from_str(GAME).unwrap();
+        let error = rename(&mut flow, "Title", "game :: Game").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`game::Game` already appears in the flow"
+        );
+        assert!(matches!(
+            rename(&mut flow, "Title", "Save"),
+            Err(Error::Exists(_))
+        ));
+        assert_eq!(renamed(GAME, "Title", "Title").1, 3);
+    }
+
+    #[test]
+    fn rust_sources_are_searched() {
+        let dir = std::env::temp_dir().join(format!("flower-rename-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/states")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "mod states;\nuse states::Game;\n").unwrap();
+        fs::write(
+            dir.join("src/states/mod.rs"),
+            "pub struct Game;\nimpl Game {\n    fn new() -> Game { Game }\n}\n// Game\n",
+        )
+        .unwrap();
+        fs::write(dir.join("target/out.rs"), "struct Game;").unwrap();
+
+        let mentions = rust_mentions(&dir, "game::Game").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let found: Vec<_> = mentions
+            .iter()
+            .map(|m| {
+                let file = m.path.strip_prefix(&dir).unwrap().display().to_string();
+                (file, m.start.line, m.start.column)
+            })
+            .collect();
+        let at = |file: &str, line, column| (file.to_string(), line, column);
+        assert_eq!(
+            found,
+            [
+                at("src/lib.rs", 2, 12),
+                at("src/states/mod.rs", 1, 11),
+                at("src/states/mod.rs", 2, 5),
+                at("src/states/mod.rs", 3, 16),
+                at("src/states/mod.rs", 3, 23),
+            ]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::raw_ast::RawFlow;
+
+pub const VERSION: u64 = 1;
+
+// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`. Documents without a
+// `version` field are version 0.
+const MIGRATIONS: [fn(&mut Map<String, Value>); VERSION as usize] = [v0_to_v1];
+
+// Version 0 is the unversioned format the editor saved before documents carried a version.
+// Its shape is the same, so there is nothing to change beyond the version itself.
+fn v0_to_v1(_document: &mut Map<String, Value>) {}
+
+/// A serialized flow: the format version followed by the flow itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS, schemars::JsonSchema), ts(rename = "Flow"))]
+#[cfg_attr(test, schemars(title = "Flow", deny_unknown_fields))]
+pub struct Document {
+    /// The format version; files without one are migrated from version 0.
+    #[cfg_attr(test, ts(type = "number"))]
+    pub version: u64,
+    #[serde(flatten)]
+    pub flow: RawFlow,
+}
+
+impl From<RawFlow> for Document {
+    fn from(flow: RawFlow) -> Self {
+        Document {
+            version: VERSION,
+            flow,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Ignores fields this version does not know, as plain serde does.
+    Lenient,
+    // Rejects fields this version does not know, after migrating.
+    Strict,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    NotAnObject,
+    UnsupportedVersion(Value),
+    UnknownFields(Vec<String>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "{}", e),
+            Error::NotAnObject => write!(f, "expected a JSON object"),
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported version {}; this build reads versions 0 to {}",
+                version, VERSION
+            ),
+            Error::UnknownFields(fields) => write!(f, "unknown fields: {}", fields.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn from_str(s: &str, mode: Mode) -> Result<RawFlow, Error> {
+    from_value(serde_json::from_str(s).map_err(Error::Json)?, mode)
+}
+
+pub fn from_value(value: Value, mode: Mode) -> Result<RawFlow, Error> {
+    let mut document = match value {
+        Value::Object(document) => document,
+        _ => return Err(Error::NotAnObject),
+    };
+    let version = match document.remove("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .filter(|v| *v <= VERSION)
+            .ok_or(Error::UnsupportedVersion(version))?,
+    };
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut document);
+    }
+    let value = Value::Object(document);
+    match mode {
+        Mode::Lenient => serde_json::from_value(value).map_err(Error::Json),
+        Mode::Strict => {
+            let mut unknown = Vec::new();
+            let flow = serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))
+                .map_err(Error::Json)?;
+            if unknown.is_empty() {
+                Ok(flow)
+            } else {
+                Err(Error::UnknownFields(unknown))
+            }
+        }
+    }
+}
+
+pub fn to_string(flow: &RawFlow) -> String {
+    serde_json::to_string(&Document::from(flow.clone())).unwrap()
+}
+
+pub fn to_string_pretty(flow: &RawFlow) -> String {
+    serde_json::to_string_pretty(&Document::from(flow.clone())).unwrap()
+}
+
+#[cfg(test)]
+mod schema_test {
+    use std::fs;
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::raw_ast::{RawReference, RawResource, RawState};
+
+    fn game() -> RawFlow {
+        RawFlow {
+            resources: vec![RawResource("Score".to_string())],
+            states: vec![RawState("Title".to_string()), RawState("Game".to_string())],
+            intermediates: vec![],
+            references: vec![RawReference {
+                state: RawState("Game".to_string()),
+                mutable: false,
+                resource: RawResource("Score".to_string()),
+            }],
+            transitions: vec![],
+            overlays: vec![],
+            layout: None,
+        }
+    }
+
+    #[test]
+    fn documents_carry_the_version() {
+        let json: Value = serde_json::from_str(&to_string(&game())).unwrap();
+        assert_eq!(json["version"], json!(VERSION));
+        assert_eq!(from_str(&to_string(&game()), Mode::Strict).unwrap(), game());
+    }
+
+    #[test]
+    fn unversioned_documents_are_migrated() {
+        let v0 = json!({
+            "resources": ["Score"],
+            "states": ["Title", "Game"],
+            "intermediates": [],
+            "references": [{ "state": "Game", "mutable": false, "resource": "Score" }],
+            "transitions": [],
+            "overlays": [],
+        });
+        assert_eq!(from_value(v0.clone(), Mode::Strict).unwrap(), game());
+
+        let mut malformed = v0;
+        malformed["references"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("mutable");
+        assert!(matches!(
+            from_value(malformed, Mode::Lenient),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut json: Value = serde_json::from_str(&to_string(&game())).unwrap();
+        json["version"] = json!(VERSION + 1);
+        assert!(matches!(
+            from_value(json, Mode::Lenient),
+            Err(Error::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_fields() {
+        let mut json: Value = serde_json::from_str(&to_string(&game())).unwrap();
+        json["comment"] = json!("hello");
+        json["references"][0]["weak"] = json!(true);
+        assert_eq!(from_value(json.clone(), Mode::Lenient).unwrap(), game());
+        match from_value(json, Mode::Strict) {
+            Err(Error::UnknownFields(fields)) => {
+                assert_eq!(fields, ["comment", "references.0.weak"])
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_schema_is_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema/flow.schema.json");
+        let schema = schemars::schema_for!(Document);
+        let expected = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, &expected).unwrap();
+        }
+        let actual = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            actual == expected,
+            "{} is stale; run `UPDATE_SNAPSHOTS=1 cargo test -p flower-parser schema`",
+            path.display()
+        );
+    }
+}
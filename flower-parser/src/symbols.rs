@@ -0,0 +1,285 @@
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::TypePath;
+
+use super::arrange::NodeKind;
+use super::ast::Flow;
+use super::diagnostic::Position;
+use super::pretty::ty;
+use super::raw_ast::RawFlow;
+
+// A name as written in flow text, with `declaration` set for its entries in `resource:`,
+// `state:` and `intermediate:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub kind: NodeKind,
+    pub name: String,
+    pub start: Position,
+    pub end: Position,
+    pub declaration: bool,
+}
+
+// Every name in the flow, in the order they are written. Layout entries take the kind of
+// their declaration and are left out when nothing declares them.
+pub fn symbols(flow: &Flow) -> Vec<Symbol> {
+    let symbol = |kind, path: &TypePath, declaration| Symbol {
+        kind,
+        name: path.to_token_stream().to_string(),
+        start: path.span().start().into(),
+        end: path.span().end().into(),
+        declaration,
+    };
+    let mut symbols = Vec::new();
+    for r in flow.resources.punct.iter() {
+        symbols.push(symbol(NodeKind::Resource, &r.0, true));
+    }
+    for s in flow.states.punct.iter() {
+        symbols.push(symbol(NodeKind::State, &s.0, true));
+    }
+    for i in flow.intermediates.punct.iter() {
+        symbols.push(symbol(NodeKind::Intermediate, &i.0, true));
+    }
+    for r in flow.references.punct.iter() {
+        symbols.push(symbol(NodeKind::State, &r.state.0, false));
+        symbols.push(symbol(NodeKind::Resource, &r.resource.0, false));
+    }
+    for t in flow.transitions.punct.iter() {
+        symbols.push(symbol(NodeKind::State, &t.from.0, false));
+        if let Some(i) = &t.intermediate {
+            symbols.push(symbol(NodeKind::Intermediate, &i.0, false));
+        }
+        symbols.push(symbol(NodeKind::State, &t.to.0, false));
+    }
+    for o in flow.overlays.punct.iter() {
+        symbols.push(symbol(NodeKind::State, &o.back.0, false));
+        symbols.push(symbol(NodeKind::State, &o.front.0, false));
+    }
+    let mut layout = Vec::new();
+    for node in flow.layout.iter().flat_map(|layout| &layout.punct) {
+        let name = node.element.to_token_stream().to_string();
+        let declared = symbols.iter().find(|s| s.declaration && s.name == name);
+        if let Some(declared) = declared {
+            layout.push(symbol(declared.kind, &node.element, false));
+        }
+    }
+    // The layout comes first in the text.
+    layout.extend(symbols);
+    layout
+}
+
+// The name under `position`, including a cursor just after its last character.
+pub fn symbol_at(symbols: &[Symbol], position: Position) -> Option<&Symbol> {
+    symbols
+        .iter()
+        .find(|s| s.start <= position && position <= s.end)
+}
+
+pub fn definition<'a>(symbols: &'a [Symbol], symbol: &Symbol) -> Option<&'a Symbol> {
+    symbols
+        .iter()
+        .find(|s| s.declaration && s.kind == symbol.kind && s.name == symbol.name)
+}
+
+pub fn references<'a>(symbols: &'a [Symbol], symbol: &Symbol) -> Vec<&'a Symbol> {
+    symbols
+        .iter()
+        .filter(|s| s.kind == symbol.kind && s.name == symbol.name)
+        .collect()
+}
+
+// Markdown describing how the element `name` takes part in the flow.
+pub fn describe(flow: &RawFlow, kind: NodeKind, name: &str) -> String {
+    let mut lines = Vec::new();
+    match kind {
+        NodeKind::State => {
+            lines.push(format!("state `{}`", ty(name)));
+            for r in flow.references.iter().filter(|r| r.state.0 == name) {
+                let mutably = if r.mutable { " mutably" } else { "" };
+                lines.push(format!("- borrows `{}`{}", ty(&r.resource.0), mutably));
+            }
+            for t in &flow.transitions {
+                let (other, direction) = if t.from.0 == name {
+                    (&t.to, "transitions to")
+                } else if t.to.0 == name {
+                    (&t.from, "is entered from")
+                } else {
+                    continue;
+                };
+                let mut line = format!("- {} `{}`", direction, ty(&other.0));
+                if let Some(via) = &t.intermediate {
+                    line += &format!(" through `{}`", ty(&via.0));
+                }
+                if let Some(ms) = t.after_ms {
+                    line += &format!(" after {} ms", ms);
+                }
+                lines.push(line);
+            }
+            for o in flow.overlays.iter().filter(|o| o.back.0 == name) {
+                lines.push(format!("- can be overlaid by `{}`", ty(&o.front.0)));
+            }
+            for o in flow.overlays.iter().filter(|o| o.front.0 == name) {
+                lines.push(format!("- overlays `{}`", ty(&o.back.0)));
+            }
+        }
+        NodeKind::Resource => {
+            lines.push(format!("resource `{}`", ty(name)));
+            for r in flow.references.iter().filter(|r| r.resource.0 == name) {
+                let mutably = if r.mutable { " mutably" } else { "" };
+                lines.push(format!("- borrowed by `{}`{}", ty(&r.state.0), mutably));
+            }
+        }
+        NodeKind::Intermediate => {
+            lines.push(format!("intermediate `{}`", ty(name)));
+            let through = flow
+                .transitions
+                .iter()
+                .filter(|t| t.intermediate.as_ref().is_some_and(|i| i.0 == name));
+            for t in through {
+                lines.push(format!(
+                    "- leads from `{}` to `{}`",
+                    ty(&t.from.0),
+                    ty(&t.to.0)
+                ));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+// The byte offset of `position` in `text`, clamped to the end of its line.
+fn offset(text: &str, position: Position) -> usize {
+    let mut start = 0;
+    for _ in 1..position.line {
+        match text[start..].find('\n') {
+            Some(newline) => start += newline + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[start..].split('\n').next().unwrap_or_default();
+    let column = line
+        .char_indices()
+        .nth(position.column)
+        .map_or(line.len(), |(i, _)| i);
+    start + column
+}
+
+// The kind of name that fits at `position`, judged from the text before it, since the flow
+// being typed rarely parses.
+fn expected(text: &str, position: Position) -> Option<NodeKind> {
+    let before = &text[..offset(text, position)];
+    let open = before.rfind('[')?;
+    if before[open..].contains(']') {
+        return None;
+    }
+    let item = before[..open].trim_end().strip_suffix(':')?;
+    if item.ends_with(':') {
+        return None;
+    }
+    let entry = before[open + 1..].rsplit(',').next().unwrap_or_default();
+    match item
+        .trim_end()
+        .rsplit(|c: char| !c.is_alphanumeric())
+        .next()
+    {
+        Some("reference") if entry.contains("-<") => Some(NodeKind::Resource),
+        Some("reference") => Some(NodeKind::State),
+        Some("transition") => match entry.split_once(">-") {
+            Some((_, rest)) if !rest.contains('>') && !rest.trim_start().starts_with('-') => {
+                Some(NodeKind::Intermediate)
+            }
+            _ => Some(NodeKind::State),
+        },
+        Some("overlay") => Some(NodeKind::State),
+        _ => None,
+    }
+}
+
+// The names declared in `flow` that fit at `position` in `text`, inside `reference:`,
+// `transition:` and `overlay:`.
+pub fn completions(text: &str, position: Position, flow: &RawFlow) -> Vec<(NodeKind, String)> {
+    let Some(kind) = expected(text, position) else {
+        return Vec::new();
+    };
+    let names: Vec<_> = match kind {
+        NodeKind::Resource => flow.resources.iter().map(|r| &r.0).collect(),
+        NodeKind::State => flow.states.iter().map(|s| &s.0).collect(),
+        NodeKind::Intermediate => flow.intermediates.iter().map(|i| &i.0).collect(),
+    };
+    let mut completions: Vec<(NodeKind, String)> = Vec::new();
+    for name in names {
+        let name = ty(name);
+        if !completions.iter().any(|(_, n)| *n == name) {
+            completions.push((kind, name));
+        }
+    }
+    completions
+}
+
+#[cfg(test)]
+mod symbols_test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const GAME: &str = "#[layout(Game { x: 0, y: 0 })]\n\
+        resource: [Save]\n\
+        state: [Title, Game, Pause]\n\
+        intermediate: [Start]\n\
+        reference: [Game -< mut Save, Pause -< Save]\n\
+        transition: [Title >- Start -> Game, Game >--> Title after 3s]\n\
+        overlay: [Game ^ Pause]\n";
+
+    fn at(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn mentions_lead_to_declarations() {
+        let symbols = symbols(&Flow::from_str(GAME).unwrap());
+        let game = symbol_at(&symbols, at(6, 31)).unwrap();
+        assert_eq!((game.kind, game.name.as_str()), (NodeKind::State, "Game"));
+        let declaration = definition(&symbols, game).unwrap();
+        assert_eq!((declaration.start, declaration.end), (at(3, 15), at(3, 19)));
+
+        let save = symbol_at(&symbols, at(5, 25)).unwrap();
+        let lines: Vec<_> = references(&symbols, save)
+            .iter()
+            .map(|s| (s.start.line, s.declaration))
+            .collect();
+        assert_eq!(lines, [(2, true), (5, false), (5, false)]);
+        assert_eq!(references(&symbols, game).len(), 6);
+        assert!(symbol_at(&symbols, at(5, 17)).is_none());
+    }
+
+    #[test]
+    fn states_are_described() {
+        let flow = RawFlow::from(Flow::from_str(GAME).unwrap());
+        assert_eq!(
+            describe(&flow, NodeKind::State, "Game"),
+            "state `Game`\n\
+            - borrows `Save` mutably\n\
+            - is entered from `Title` through `Start`\n\
+            - transitions to `Title` after 3000 ms\n\
+            - can be overlaid by `Pause`"
+        );
+    }
+
+    #[test]
+    fn completions_fit_the_section() {
+        let flow = RawFlow::from(Flow::from_str(GAME).unwrap());
+        let text = "resource: [Save]\nstate: [Title, Game]\nintermediate: [Start]\n\
+            reference: [Game -< mut Save, Pause -< ]\n\
+            transition: [Title >- , Title >--> ]\noverlay: [";
+        let kinds = |line, column| {
+            let completions = completions(text, at(line, column), &flow);
+            let kind = completions.first().map(|(kind, _)| *kind);
+            (kind, completions.len())
+        };
+        assert_eq!(kinds(1, 11), (None, 0));
+        assert_eq!(kinds(4, 12), (Some(NodeKind::State), 3));
+        assert_eq!(kinds(4, 39), (Some(NodeKind::Resource), 1));
+        assert_eq!(kinds(5, 22), (Some(NodeKind::Intermediate), 1));
+        assert_eq!(kinds(5, 35), (Some(NodeKind::State), 3));
+        assert_eq!(kinds(6, 10), (Some(NodeKind::State), 3));
+    }
+}
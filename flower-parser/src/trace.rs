@@ -0,0 +1,173 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::interpreter::{self, Interpreter, Step};
+use super::raw_ast::RawFlow;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEntry {
+    Start {
+        state: String,
+    },
+    Step {
+        step: Step,
+    },
+    Event {
+        event: String,
+        consumed_by: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+    NotStarted {
+        entry: usize,
+    },
+    Diverged {
+        entry: usize,
+        error: interpreter::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json { line, error } => write!(f, "line {}: {}", line, error),
+            Error::NotStarted { entry } => {
+                write!(
+                    f,
+                    "entry {}: trace does not begin with a `start` entry",
+                    entry
+                )
+            }
+            Error::Diverged { entry, error } => write!(f, "entry {}: {}", entry, error),
+        }
+    }
+}
+
+// One JSON object per line, as written by the runtime and the interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace(pub Vec<TraceEntry>);
+
+impl Trace {
+    pub fn to_json_lines(&self) -> String {
+        self.0
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap() + "\n")
+            .collect()
+    }
+
+    pub fn from_json_lines(s: &str) -> Result<Self, Error> {
+        s.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|error| Error::Json {
+                    line: index + 1,
+                    error,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Trace)
+    }
+
+    // Re-runs the trace against `flow`, returning the interpreter in its final configuration
+    // or the first entry (1-based, not counting blank lines) that the flow no longer allows.
+    pub fn replay(&self, flow: &RawFlow) -> Result<Interpreter, Error> {
+        let mut interpreter: Option<Interpreter> = None;
+        for (index, traced) in self.0.iter().enumerate() {
+            let entry = index + 1;
+            let diverged = |error| Error::Diverged { entry, error };
+            match traced {
+                TraceEntry::Start { state } => {
+                    interpreter = Some(Interpreter::new(flow.clone(), state).map_err(diverged)?);
+                }
+                TraceEntry::Step { step } => {
+                    let interpreter = interpreter.as_mut().ok_or(Error::NotStarted { entry })?;
+                    interpreter.apply(step).map_err(diverged)?;
+                }
+                TraceEntry::Event { consumed_by, .. } => {
+                    let interpreter = interpreter.as_ref().ok_or(Error::NotStarted { entry })?;
+                    if let Some(state) = consumed_by {
+                        if !interpreter.stack().contains(state) {
+                            return Err(diverged(interpreter::Error::NotOnStack(state.clone())));
+                        }
+                    }
+                }
+            }
+        }
+        interpreter.ok_or(Error::NotStarted { entry: 1 })
+    }
+}
+
+#[cfg(test)]
+mod trace_test {
+    use std::str::FromStr;
+
+    use super::{Error, Trace};
+    use crate::interpreter::{self, Interpreter, Step};
+    use crate::{Flow, RawFlow};
+
+    fn raw_flow(s: &str) -> RawFlow {
+        Flow::from_str(s).unwrap().into()
+    }
+
+    const FLOW_STR: &str = r#"resource: []
+state: [Title, Game, Pause]
+intermediate: [Start]
+reference: []
+transition: [Title >- Start -> Game]
+overlay: [Game ^ Pause]
+"#;
+
+    const TRACE_STR: &str = r#"{"kind":"start","state":"Title"}
+{"kind":"step","step":{"kind":"transition","from":"Title","intermediate":"Start","to":"Game"}}
+{"kind":"event","event":"Escape","consumed_by":"Game"}
+{"kind":"step","step":{"kind":"push","back":"Game","front":"Pause"}}
+{"kind":"step","step":{"kind":"pop","front":"Pause"}}
+"#;
+
+    #[test]
+    fn interpreter_trace_round_trips() {
+        let flow = raw_flow(FLOW_STR);
+        let mut interpreter = Interpreter::new(flow.clone(), "Title").unwrap();
+        let step = interpreter.enabled().remove(0);
+        interpreter.apply(&step).unwrap();
+        let push = Step::Push {
+            back: "Game".to_string(),
+            front: "Pause".to_string(),
+        };
+        interpreter.apply(&push).unwrap();
+
+        let lines = interpreter.trace().to_json_lines();
+        let trace = Trace::from_json_lines(&lines).unwrap();
+        assert_eq!(&trace, interpreter.trace());
+        assert_eq!(trace.replay(&flow).unwrap().stack(), ["Game", "Pause"]);
+    }
+
+    #[test]
+    fn replay_flags_first_disallowed_step() {
+        let trace = Trace::from_json_lines(TRACE_STR).unwrap();
+        assert!(trace.replay(&raw_flow(FLOW_STR)).is_ok());
+
+        let changed = raw_flow(&FLOW_STR.replace("overlay: [Game ^ Pause]", "overlay: []"));
+        match trace.replay(&changed) {
+            Err(
+                e @ Error::Diverged {
+                    entry: 4,
+                    error: interpreter::Error::NotEnabled(_),
+                },
+            ) => assert!(e.to_string().starts_with("entry 4: ")),
+            other => panic!(
+                "unexpected replay result: {:?}",
+                other.map(|i| i.stack().to_vec())
+            ),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+
+use ts_rs::TS;
+
+use super::arrange::{Arrangement, Edge, EdgeKind, Node, NodeKind, Point};
+use super::diagnostic::{Diagnostic, Outcome, Position, Severity};
+use super::edit::{Edit, Entries};
+use super::raw_ast::*;
+use super::schema::Document;
+
+const HEADER: &str =
+    "// Generated from flower-parser's raw_ast, schema, diagnostic, arrange and edit types; do not edit.
+// Run `UPDATE_SNAPSHOTS=1 cargo test -p flower-parser typescript` to regenerate.
+";
+
+fn export<T: TS>() -> String {
+    format!("\n{}export {}\n", T::DOCS.unwrap_or(""), T::decl())
+}
+
+fn declarations() -> String {
+    let decls = [
+        export::<RawResource>(),
+        export::<RawState>(),
+        export::<RawIntermediate>(),
+        export::<RawReference>(),
+        export::<RawTransition>(),
+        export::<RawOverlay>(),
+        export::<RawLayoutNode>(),
+        export::<Document>(),
+        export::<Severity>(),
+        export::<Position>(),
+        export::<Diagnostic>(),
+        export::<Outcome<RawFlow>>(),
+        export::<NodeKind>(),
+        export::<Point>(),
+        export::<Node>(),
+        export::<EdgeKind>(),
+        export::<Edge>(),
+        export::<Arrangement>(),
+        export::<Edit>(),
+        export::<Entries>(),
+    ];
+    HEADER.to_string() + &decls.concat()
+}
+
+#[cfg(test)]
+mod typescript_test {
+    use super::*;
+
+    #[test]
+    fn editor_types_are_up_to_date() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../flower-editor/src/@types/flow.d.ts");
+        let expected = declarations();
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&path, &expected).unwrap();
+        }
+        let actual = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            actual == expected,
+            "{} is stale; run `UPDATE_SNAPSHOTS=1 cargo test -p flower-parser typescript`",
+            path.display()
+        );
+    }
+}
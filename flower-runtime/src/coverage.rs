@@ -0,0 +1,73 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Mutex;
+
+use flower_parser::coverage::{
+    overlay_key, process_report, transition_key, CoverageReport, FlowCoverage,
+};
+
+use super::record::Record;
+use super::Flow;
+
+// Serializes the read-merge-write of the report between drivers dropped on different test threads.
+// Holds whether this process has written its part yet: a part left behind by an earlier process
+// with the same pid is overwritten rather than merged into.
+static REPORT: Mutex<bool> = Mutex::new(false);
+
+pub(crate) fn count(coverage: &mut FlowCoverage, flow: &Flow, record: &Record) {
+    let mut enter = |state| {
+        *coverage
+            .states
+            .entry(flow.name_of(state).to_string())
+            .or_default() += 1
+    };
+    match *record {
+        Record::Start(state) => enter(state),
+        Record::Transition { from, via, to, .. } => {
+            enter(to);
+            let intermediate = (via != to).then(|| flow.name_of(via));
+            let key = transition_key(flow.name_of(from), intermediate, flow.name_of(to));
+            *coverage.transitions.entry(key).or_default() += 1;
+        }
+        Record::Push { back, front } => {
+            enter(front);
+            let key = overlay_key(flow.name_of(back), flow.name_of(front));
+            *coverage.overlays.entry(key).or_default() += 1;
+        }
+        Record::Pop(_) | Record::Event { .. } => {}
+    }
+}
+
+// Merges `coverage` into this process's part of the JSON report named by `FLOWER_COVERAGE`,
+// if it is set. `flower coverage` reads the parts together.
+pub(crate) fn flush(module: &str, coverage: &FlowCoverage) {
+    let path = match env::var_os("FLOWER_COVERAGE") {
+        Some(path) => process_report(Path::new(&path), process::id()),
+        None => return,
+    };
+    let mut written = REPORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut report = CoverageReport::default();
+    if *written {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(parsed) => report = parsed,
+            Err(e) => {
+                eprintln!("flower-runtime: failed to read coverage report: {}", e);
+                return;
+            }
+        }
+    }
+    report
+        .flows
+        .entry(module.to_string())
+        .or_default()
+        .merge(coverage);
+    match fs::write(&path, serde_json::to_string_pretty(&report).unwrap()) {
+        Ok(()) => *written = true,
+        Err(e) => eprintln!("flower-runtime: failed to write coverage report: {}", e),
+    }
+}
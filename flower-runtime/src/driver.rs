@@ -0,0 +1,681 @@
+use std::any::{Any, TypeId};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::join::join_all;
+use super::record::Record;
+use super::{AsyncState, Clock, Context, Control, Flow, Resources, Response, SystemClock};
+
+struct Entry {
+    id: TypeId,
+    state: Box<dyn AsyncState>,
+    // Moved forward by the time spent covered by overlays, so timers count time on top.
+    entered_at: Duration,
+    covered_at: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Finished,
+    UndeclaredTransition {
+        from: &'static str,
+        via: &'static str,
+    },
+    UndeclaredOverlay {
+        back: &'static str,
+        front: &'static str,
+    },
+    CoveredControl {
+        state: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Finished => write!(f, "the state stack is empty"),
+            Error::UndeclaredTransition { from, via } => {
+                write!(f, "no transition from `{}` via `{}`", from, via)
+            }
+            Error::UndeclaredOverlay { back, front } => {
+                write!(f, "overlay not declared: `{} ^ {}`", back, front)
+            }
+            Error::CoveredControl { state } => write!(
+                f,
+                "state `{}` is covered by an overlay and can only continue",
+                state
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub struct Driver {
+    flow: Flow,
+    resources: Resources,
+    stack: Vec<Entry>,
+    events: VecDeque<(&'static str, Box<dyn Any>)>,
+    clock: Box<dyn Clock>,
+    records: Option<Vec<Record>>,
+    #[cfg(feature = "coverage")]
+    coverage: flower_parser::coverage::FlowCoverage,
+    entered: bool,
+}
+
+impl Driver {
+    pub fn new<S: AsyncState>(flow: Flow, resources: Resources, initial: S) -> Self {
+        let stack = vec![Entry {
+            id: TypeId::of::<S>(),
+            state: Box::new(initial),
+            entered_at: Duration::ZERO,
+            covered_at: None,
+        }];
+        Driver {
+            flow,
+            resources,
+            stack,
+            events: VecDeque::new(),
+            clock: Box::new(SystemClock::default()),
+            records: None,
+            #[cfg(feature = "coverage")]
+            coverage: Default::default(),
+            entered: false,
+        }
+    }
+
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    #[cfg(feature = "trace")]
+    pub fn recording(mut self) -> Self {
+        self.records = Some(Vec::new());
+        self
+    }
+
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> flower_parser::trace::Trace {
+        super::record::to_trace(self.records.as_deref().unwrap_or_default(), &self.flow)
+    }
+
+    pub fn stack(&self) -> Vec<&'static str> {
+        self.stack.iter().map(|e| self.flow.name_of(e.id)).collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn send<E: 'static>(&mut self, event: E) {
+        let name = std::any::type_name::<E>();
+        self.events.push_back((name, Box::new(event)));
+    }
+
+    pub async fn run(&mut self) -> Result<(), Error> {
+        while !self.is_finished() {
+            self.step().await?;
+        }
+        Ok(())
+    }
+
+    // Awaits the top-of-stack state, together with the states it covers as long as
+    // none of them share a resource that one of them borrows mutably.
+    pub async fn step(&mut self) -> Result<(), Error> {
+        self.enter_initial().await?;
+        if self.fire_timer().await {
+            return Ok(());
+        }
+
+        let start = self.running_from();
+        let Driver {
+            flow,
+            resources,
+            stack,
+            ..
+        } = self;
+        let futures = stack[start..]
+            .iter_mut()
+            .map(|e| e.state.update(Context::new(flow, resources, e.id)))
+            .collect();
+        let mut controls = join_all(futures).await;
+
+        let control = controls.pop().unwrap();
+        for (entry, covered) in self.stack[start..].iter().zip(controls) {
+            if !matches!(covered, Control::Continue) {
+                let state = self.flow.name_of(entry.id);
+                return Err(Error::CoveredControl { state });
+            }
+        }
+        self.apply(control).await
+    }
+
+    // Delivers every queued event to the top of the stack first. An event that is
+    // ignored bubbles down to the covered states; a state that consumes it with a
+    // control other than `Continue` first has the overlays above it exited.
+    pub async fn dispatch(&mut self) -> Result<(), Error> {
+        self.enter_initial().await?;
+        self.fire_timer().await;
+
+        while let Some((name, event)) = self.events.pop_front() {
+            let mut consumed_by = None;
+            for index in (0..self.stack.len()).rev() {
+                let Driver {
+                    flow,
+                    resources,
+                    stack,
+                    ..
+                } = self;
+                let entry = &mut stack[index];
+                let response = entry
+                    .state
+                    .on_event(Context::new(flow, resources, entry.id), &*event)
+                    .await;
+                let control = match response {
+                    Response::Ignore => continue,
+                    Response::Consume(control) => control,
+                };
+                consumed_by = Some(self.stack[index].id);
+                self.record(Record::Event {
+                    event: name,
+                    consumed_by,
+                });
+                if !matches!(control, Control::Continue) {
+                    while self.stack.len() > index + 1 {
+                        let front = self.stack.last().unwrap().id;
+                        self.record(Record::Pop(front));
+                        self.exit().await;
+                    }
+                    self.apply(control).await?;
+                }
+                break;
+            }
+            if consumed_by.is_none() {
+                self.record(Record::Event {
+                    event: name,
+                    consumed_by,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn enter_initial(&mut self) -> Result<(), Error> {
+        if self.stack.is_empty() {
+            return Err(Error::Finished);
+        }
+        if !self.entered {
+            self.entered = true;
+            let Driver {
+                flow,
+                resources,
+                stack,
+                clock,
+                ..
+            } = self;
+            let entry = stack.last_mut().unwrap();
+            entry.entered_at = clock.now();
+            entry
+                .state
+                .on_enter(Context::new(flow, resources, entry.id))
+                .await;
+            let id = entry.id;
+            self.record(Record::Start(id));
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, record: Record) {
+        #[cfg(feature = "coverage")]
+        super::coverage::count(&mut self.coverage, &self.flow, &record);
+        if let Some(records) = &mut self.records {
+            records.push(record);
+        }
+    }
+
+    // Takes the earliest timed transition of the top state whose delay has elapsed.
+    async fn fire_timer(&mut self) -> bool {
+        let Some(top) = self.stack.last() else {
+            return false;
+        };
+        let elapsed = self.clock.now() - top.entered_at;
+        match self.flow.next_timer(top.id) {
+            Some((transition, timer)) if timer.after <= elapsed => {
+                let transition = *transition;
+                self.record(Record::Transition {
+                    from: transition.from,
+                    via: transition.via,
+                    to: transition.to,
+                    after: Some(timer.after),
+                });
+                self.exit().await;
+                let state = (transition.construct)((timer.via)());
+                self.enter(transition.to, state).await;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn running_from(&self) -> usize {
+        let mut start = self.stack.len() - 1;
+        while start > 0 {
+            let below = self.stack[start - 1].id;
+            if self.stack[start..]
+                .iter()
+                .any(|e| self.flow.conflicts(e.id, below))
+            {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    async fn apply(&mut self, control: Control) -> Result<(), Error> {
+        let top = self.stack.last().unwrap().id;
+        match control {
+            Control::Continue => {}
+            Control::Transition(via) => {
+                let via_id = (*via).type_id();
+                let transition = *self.flow.find_transition(top, via_id).ok_or_else(|| {
+                    Error::UndeclaredTransition {
+                        from: self.flow.name_of(top),
+                        via: self.flow.name_of(via_id),
+                    }
+                })?;
+                self.record(Record::Transition {
+                    from: top,
+                    via: via_id,
+                    to: transition.to,
+                    after: transition.timer.map(|timer| timer.after),
+                });
+                self.exit().await;
+                self.enter(transition.to, (transition.construct)(via)).await;
+            }
+            Control::Push(front, state) => {
+                if !self.flow.has_overlay(top, front) {
+                    return Err(Error::UndeclaredOverlay {
+                        back: self.flow.name_of(top),
+                        front: self.flow.name_of(front),
+                    });
+                }
+                self.record(Record::Push { back: top, front });
+                self.stack.last_mut().unwrap().covered_at = Some(self.clock.now());
+                self.enter(front, state).await;
+            }
+            Control::Pop => {
+                self.record(Record::Pop(top));
+                self.exit().await;
+                let now = self.clock.now();
+                if let Some(back) = self.stack.last_mut() {
+                    if let Some(covered_at) = back.covered_at.take() {
+                        back.entered_at += now - covered_at;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn enter(&mut self, id: TypeId, state: Box<dyn AsyncState>) {
+        let entered_at = self.clock.now();
+        self.stack.push(Entry {
+            id,
+            state,
+            entered_at,
+            covered_at: None,
+        });
+        let entry = self.stack.last_mut().unwrap();
+        entry
+            .state
+            .on_enter(Context::new(&self.flow, &self.resources, id))
+            .await;
+    }
+
+    async fn exit(&mut self) {
+        let mut entry = self.stack.pop().unwrap();
+        entry
+            .state
+            .on_exit(Context::new(&self.flow, &self.resources, entry.id))
+            .await;
+    }
+}
+
+#[cfg(feature = "coverage")]
+impl Drop for Driver {
+    fn drop(&mut self) {
+        super::coverage::flush(self.flow.module, &self.coverage);
+    }
+}
+
+#[cfg(test)]
+mod driver_test {
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context as TaskContext, Poll};
+    use std::time::Duration;
+
+    use super::{Driver, Error};
+    use crate::executor::block_on;
+    use crate::{
+        AsyncState, Context, Control, Flow, LocalBoxFuture, ManualClock, Resources, Response,
+    };
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    struct Save(u32);
+
+    // Returns `Pending` once so that concurrently joined futures interleave.
+    struct YieldNow(bool);
+    impl Future for YieldNow {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    struct Title(Log);
+    impl AsyncState for Title {
+        fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+            Box::pin(async move { Control::transition(Start(self.0.clone())) })
+        }
+        fn on_exit<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+            Box::pin(async move { self.0.borrow_mut().push("exit title") })
+        }
+    }
+
+    struct Start(Log);
+    struct Game(Log, u32);
+    impl From<Start> for Game {
+        fn from(start: Start) -> Self {
+            Game(start.0, 0)
+        }
+    }
+    impl AsyncState for Game {
+        fn on_enter<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+            Box::pin(async move { self.0.borrow_mut().push("enter game") })
+        }
+        fn update<'a>(&'a mut self, cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+            Box::pin(async move {
+                self.1 += 1;
+                if self.1 == 1 {
+                    return Control::push(Pause(self.0.clone()));
+                }
+                cx.get_mut::<Save>().0 += 1;
+                self.0.borrow_mut().push("game");
+                YieldNow(false).await;
+                self.0.borrow_mut().push("game done");
+                Control::Continue
+            })
+        }
+    }
+
+    struct Pause(Log);
+    impl AsyncState for Pause {
+        fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+            Box::pin(async move {
+                self.0.borrow_mut().push("pause");
+                YieldNow(false).await;
+                self.0.borrow_mut().push("pause done");
+                Control::Pop
+            })
+        }
+    }
+
+    fn flow() -> Flow {
+        Flow::new()
+            .resource::<Save>("Save")
+            .state::<Title>("Title")
+            .state::<Game>("Game")
+            .state::<Pause>("Pause")
+            .intermediate::<Start>("Start")
+            .reference::<Game, Save>(true)
+            .transition::<Title, Start, Game>()
+            .overlay::<Game, Pause>()
+    }
+
+    fn run(flow: Flow) -> Vec<&'static str> {
+        let log = Log::default();
+        let resources = Resources::new().with(Save(0));
+        let mut driver = Driver::new(flow, resources, Title(log.clone()));
+        block_on(async {
+            driver.step().await.unwrap();
+            assert_eq!(driver.stack(), ["Game"]);
+            driver.step().await.unwrap();
+            assert_eq!(driver.stack(), ["Game", "Pause"]);
+            driver.step().await.unwrap();
+            assert_eq!(driver.stack(), ["Game"]);
+        });
+        let log = log.borrow().clone();
+        log
+    }
+
+    #[test]
+    fn overlay_runs_concurrently() {
+        assert_eq!(
+            run(flow()),
+            [
+                "exit title",
+                "enter game",
+                "game",
+                "pause",
+                "game done",
+                "pause done"
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_sharing_mutable_resource_runs_alone() {
+        assert_eq!(
+            run(flow().reference::<Pause, Save>(false)),
+            ["exit title", "enter game", "pause", "pause done"]
+        );
+    }
+
+    #[test]
+    fn undeclared_transition() {
+        let flow = Flow::new().state::<Title>("Title");
+        let mut driver = Driver::new(flow, Resources::new(), Title(Log::default()));
+        let result = block_on(driver.step());
+        assert!(matches!(
+            result,
+            Err(Error::UndeclaredTransition {
+                from: "Title",
+                via: "<undeclared>"
+            })
+        ));
+    }
+
+    #[derive(PartialEq)]
+    enum Key {
+        Escape,
+        Jump,
+        Quit,
+    }
+
+    struct Field(Log);
+    impl AsyncState for Field {
+        fn on_event<'a>(
+            &'a mut self,
+            _cx: Context<'a>,
+            event: &'a dyn Any,
+        ) -> LocalBoxFuture<'a, Response> {
+            Box::pin(async move {
+                match event.downcast_ref::<Key>() {
+                    Some(Key::Escape) => Response::Consume(Control::push(Menu(self.0.clone()))),
+                    Some(Key::Jump) => {
+                        self.0.borrow_mut().push("jump");
+                        Response::consume()
+                    }
+                    Some(Key::Quit) => Response::Consume(Control::transition(Ended)),
+                    None => Response::Ignore,
+                }
+            })
+        }
+    }
+
+    struct Menu(Log);
+    impl AsyncState for Menu {
+        fn on_event<'a>(
+            &'a mut self,
+            _cx: Context<'a>,
+            event: &'a dyn Any,
+        ) -> LocalBoxFuture<'a, Response> {
+            Box::pin(async move {
+                if event.downcast_ref() == Some(&Key::Escape) {
+                    Response::Consume(Control::Pop)
+                } else {
+                    Response::Ignore
+                }
+            })
+        }
+        fn on_exit<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+            Box::pin(async move { self.0.borrow_mut().push("exit menu") })
+        }
+    }
+
+    struct Ended;
+    impl AsyncState for Ended {}
+
+    #[test]
+    fn events_bubble_from_the_top_overlay() {
+        let flow = Flow::new()
+            .state::<Field>("Field")
+            .state::<Menu>("Menu")
+            .state::<Ended>("Ended")
+            .transition::<Field, Ended, Ended>()
+            .overlay::<Field, Menu>();
+        let log = Log::default();
+        let mut driver = Driver::new(flow, Resources::new(), Field(log.clone()));
+        block_on(async {
+            driver.send(Key::Escape);
+            driver.send(Key::Jump);
+            driver.send("unknown event");
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Field", "Menu"]);
+
+            driver.send(Key::Escape);
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Field"]);
+
+            driver.send(Key::Escape);
+            driver.send(Key::Quit);
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Ended"]);
+        });
+        assert_eq!(*log.borrow(), ["jump", "exit menu", "exit menu"]);
+    }
+
+    struct Splash;
+    impl AsyncState for Splash {}
+
+    #[derive(Default)]
+    struct Attract;
+    struct MainMenu;
+    impl From<Attract> for MainMenu {
+        fn from(_: Attract) -> Self {
+            MainMenu
+        }
+    }
+    impl AsyncState for MainMenu {}
+
+    #[test]
+    fn timed_transition_fires_on_clock() {
+        let flow = Flow::new()
+            .state::<Splash>("Splash")
+            .state::<MainMenu>("MainMenu")
+            .intermediate::<Attract>("Attract")
+            .timed_transition::<Splash, Attract, MainMenu>(Duration::from_secs(3));
+        let clock = ManualClock::new();
+        let mut driver = Driver::new(flow, Resources::new(), Splash).with_clock(clock.clone());
+        block_on(async {
+            clock.advance(Duration::from_secs(1));
+            driver.step().await.unwrap();
+            clock.advance(Duration::from_millis(2999));
+            driver.step().await.unwrap();
+            assert_eq!(driver.stack(), ["Splash"]);
+
+            clock.advance(Duration::from_millis(1));
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["MainMenu"]);
+        });
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn traces_record_the_timer_that_fired() {
+        use flower_parser::interpreter::Step;
+        use flower_parser::trace::TraceEntry;
+
+        let flow = Flow::new()
+            .state::<Splash>("Splash")
+            .state::<MainMenu>("MainMenu")
+            .intermediate::<Attract>("Attract")
+            .transition::<Splash, Attract, MainMenu>()
+            .timed_transition::<Splash, Attract, MainMenu>(Duration::from_secs(3));
+        let clock = ManualClock::new();
+        let mut driver = Driver::new(flow, Resources::new(), Splash)
+            .with_clock(clock.clone())
+            .recording();
+        block_on(async {
+            driver.dispatch().await.unwrap();
+            clock.advance(Duration::from_secs(3));
+            driver.dispatch().await.unwrap();
+        });
+        assert_eq!(driver.stack(), ["MainMenu"]);
+        match &driver.trace().0[1] {
+            TraceEntry::Step {
+                step: Step::Transition { after_ms, .. },
+            } => assert_eq!(*after_ms, Some(3000)),
+            entry => panic!("unexpected entry: {:?}", entry),
+        }
+    }
+
+    #[test]
+    fn timers_pause_while_covered() {
+        let flow = Flow::new()
+            .state::<Field>("Field")
+            .state::<Menu>("Menu")
+            .state::<MainMenu>("MainMenu")
+            .intermediate::<Attract>("Attract")
+            .overlay::<Field, Menu>()
+            .timed_transition::<Field, Attract, MainMenu>(Duration::from_secs(3));
+        let clock = ManualClock::new();
+        let log = Log::default();
+        let mut driver =
+            Driver::new(flow, Resources::new(), Field(log.clone())).with_clock(clock.clone());
+        block_on(async {
+            driver.dispatch().await.unwrap();
+            clock.advance(Duration::from_secs(1));
+            driver.send(Key::Escape);
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Field", "Menu"]);
+
+            clock.advance(Duration::from_secs(10));
+            driver.send(Key::Escape);
+            driver.dispatch().await.unwrap();
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Field"]);
+
+            clock.advance(Duration::from_millis(1999));
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["Field"]);
+            clock.advance(Duration::from_millis(1));
+            driver.dispatch().await.unwrap();
+            assert_eq!(driver.stack(), ["MainMenu"]);
+        });
+    }
+}
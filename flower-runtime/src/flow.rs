@@ -0,0 +1,197 @@
+use std::any::{Any, TypeId};
+use std::time::Duration;
+
+use super::AsyncState;
+
+pub(crate) type Construct = fn(Box<dyn Any>) -> Box<dyn AsyncState>;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Node {
+    pub id: TypeId,
+    pub name: &'static str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Reference {
+    pub state: TypeId,
+    pub resource: TypeId,
+    pub mutable: bool,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Transition {
+    pub from: TypeId,
+    pub via: TypeId,
+    pub to: TypeId,
+    pub construct: Construct,
+    pub timer: Option<Timer>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Timer {
+    pub after: Duration,
+    pub via: fn() -> Box<dyn Any>,
+}
+
+impl Transition {
+    fn new<S, V, T>(timer: Option<Timer>) -> Self
+    where
+        S: 'static,
+        V: 'static,
+        T: AsyncState + From<V>,
+    {
+        Transition {
+            from: TypeId::of::<S>(),
+            via: TypeId::of::<V>(),
+            to: TypeId::of::<T>(),
+            construct: |via| {
+                let via = via
+                    .downcast::<V>()
+                    .unwrap_or_else(|_| unreachable!("transition value checked by the driver"));
+                Box::new(T::from(*via))
+            },
+            timer,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Overlay {
+    pub back: TypeId,
+    pub front: TypeId,
+}
+
+#[derive(Default)]
+pub struct Flow {
+    pub(crate) module: &'static str,
+    pub(crate) resources: Vec<Node>,
+    pub(crate) states: Vec<Node>,
+    pub(crate) intermediates: Vec<Node>,
+    pub(crate) references: Vec<Reference>,
+    pub(crate) transitions: Vec<Transition>,
+    pub(crate) overlays: Vec<Overlay>,
+}
+
+impl Flow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Identifies the flow in coverage reports; `flow!` passes its `module_path!()`.
+    pub fn module(mut self, module: &'static str) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn resource<R: 'static>(mut self, name: &'static str) -> Self {
+        self.resources.push(Node {
+            id: TypeId::of::<R>(),
+            name,
+        });
+        self
+    }
+
+    pub fn state<S: 'static>(mut self, name: &'static str) -> Self {
+        self.states.push(Node {
+            id: TypeId::of::<S>(),
+            name,
+        });
+        self
+    }
+
+    pub fn intermediate<N: 'static>(mut self, name: &'static str) -> Self {
+        self.intermediates.push(Node {
+            id: TypeId::of::<N>(),
+            name,
+        });
+        self
+    }
+
+    pub fn reference<S: 'static, R: 'static>(mut self, mutable: bool) -> Self {
+        self.references.push(Reference {
+            state: TypeId::of::<S>(),
+            resource: TypeId::of::<R>(),
+            mutable,
+        });
+        self
+    }
+
+    // `S >--> T` is registered as `transition::<S, T, T>()`.
+    pub fn transition<S, V, T>(mut self) -> Self
+    where
+        S: 'static,
+        V: 'static,
+        T: AsyncState + From<V>,
+    {
+        self.transitions.push(Transition::new::<S, V, T>(None));
+        self
+    }
+
+    // `S >- V -> T after 3s` fires once `S` has been on top for `after`, with `V::default()`.
+    // Time spent covered by an overlay does not count.
+    pub fn timed_transition<S, V, T>(mut self, after: Duration) -> Self
+    where
+        S: 'static,
+        V: Default + 'static,
+        T: AsyncState + From<V>,
+    {
+        let timer = Timer {
+            after,
+            via: || Box::new(V::default()),
+        };
+        self.transitions
+            .push(Transition::new::<S, V, T>(Some(timer)));
+        self
+    }
+
+    pub fn overlay<B: 'static, F: 'static>(mut self) -> Self {
+        self.overlays.push(Overlay {
+            back: TypeId::of::<B>(),
+            front: TypeId::of::<F>(),
+        });
+        self
+    }
+
+    pub(crate) fn name_of(&self, id: TypeId) -> &'static str {
+        self.states
+            .iter()
+            .chain(&self.intermediates)
+            .chain(&self.resources)
+            .find(|node| node.id == id)
+            .map_or("<undeclared>", |node| node.name)
+    }
+
+    pub(crate) fn references_of(&self, state: TypeId) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.state == state)
+    }
+
+    // Prefers an untimed transition, since timed ones are normally taken by their timer.
+    pub(crate) fn find_transition(&self, from: TypeId, via: TypeId) -> Option<&Transition> {
+        self.transitions
+            .iter()
+            .filter(|t| t.from == from && t.via == via)
+            .min_by_key(|t| t.timer.is_some())
+    }
+
+    pub(crate) fn next_timer(&self, from: TypeId) -> Option<(&Transition, Timer)> {
+        self.transitions
+            .iter()
+            .filter(|t| t.from == from)
+            .filter_map(|t| t.timer.map(|timer| (t, timer)))
+            .min_by_key(|(_, timer)| timer.after)
+    }
+
+    pub(crate) fn has_overlay(&self, back: TypeId, front: TypeId) -> bool {
+        self.overlays
+            .iter()
+            .any(|o| o.back == back && o.front == front)
+    }
+
+    // Two states conflict when they reference the same resource and at least one of them does so mutably.
+    pub fn conflicts(&self, a: TypeId, b: TypeId) -> bool {
+        self.references_of(a).any(|ra| {
+            self.references_of(b)
+                .any(|rb| ra.resource == rb.resource && (ra.mutable || rb.mutable))
+        })
+    }
+}
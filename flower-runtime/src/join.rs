@@ -0,0 +1,42 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::LocalBoxFuture;
+
+// Polls every future on each wake-up; resolves once all of them are ready.
+pub(crate) struct JoinAll<'a, T> {
+    futures: Vec<Option<LocalBoxFuture<'a, T>>>,
+    outputs: Vec<Option<T>>,
+}
+
+pub(crate) fn join_all<T>(futures: Vec<LocalBoxFuture<'_, T>>) -> JoinAll<'_, T> {
+    let outputs = futures.iter().map(|_| None).collect();
+    let futures = futures.into_iter().map(Some).collect();
+    JoinAll { futures, outputs }
+}
+
+impl<'a, T: Unpin> Future for JoinAll<'a, T> {
+    type Output = Vec<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut pending = false;
+        for (slot, output) in this.futures.iter_mut().zip(&mut this.outputs) {
+            if let Some(future) = slot {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => {
+                        *output = Some(value);
+                        *slot = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(this.outputs.iter_mut().map(|o| o.take().unwrap()).collect())
+        }
+    }
+}
@@ -0,0 +1,18 @@
+mod clock;
+#[cfg(feature = "coverage")]
+mod coverage;
+mod driver;
+pub mod executor;
+mod flow;
+mod join;
+mod meta;
+mod record;
+mod resource;
+mod state;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use driver::{Driver, Error};
+pub use flow::Flow;
+pub use meta::{FlowMeta, OverlayMeta, ReferenceMeta, TransitionMeta};
+pub use resource::{Context, Resources};
+pub use state::{AsyncState, Control, LocalBoxFuture, Response};
@@ -0,0 +1,100 @@
+// A `const`-constructible description of a flow, emitted by `flow!` as `FLOW`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowMeta {
+    pub resources: &'static [&'static str],
+    pub states: &'static [&'static str],
+    pub intermediates: &'static [&'static str],
+    pub references: &'static [ReferenceMeta],
+    pub transitions: &'static [TransitionMeta],
+    pub overlays: &'static [OverlayMeta],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReferenceMeta {
+    pub state: &'static str,
+    pub mutable: bool,
+    pub resource: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionMeta {
+    pub from: &'static str,
+    pub intermediate: Option<&'static str>,
+    pub to: &'static str,
+    pub after_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayMeta {
+    pub back: &'static str,
+    pub front: &'static str,
+}
+
+impl FlowMeta {
+    pub fn references_of<'a>(&'a self, state: &'a str) -> impl Iterator<Item = &'a ReferenceMeta> {
+        self.references.iter().filter(move |r| r.state == state)
+    }
+
+    pub fn transitions_from<'a>(
+        &'a self,
+        state: &'a str,
+    ) -> impl Iterator<Item = &'a TransitionMeta> {
+        self.transitions.iter().filter(move |t| t.from == state)
+    }
+
+    pub fn overlays_on<'a>(&'a self, state: &'a str) -> impl Iterator<Item = &'a OverlayMeta> {
+        self.overlays.iter().filter(move |o| o.back == state)
+    }
+}
+
+#[cfg(feature = "flower-parser")]
+impl From<&FlowMeta> for flower_parser::RawFlow {
+    fn from(meta: &FlowMeta) -> Self {
+        use flower_parser::{
+            RawIntermediate, RawOverlay, RawReference, RawResource, RawState, RawTransition,
+        };
+
+        let state = |s: &str| RawState(s.to_string());
+        flower_parser::RawFlow {
+            resources: meta
+                .resources
+                .iter()
+                .map(|r| RawResource(r.to_string()))
+                .collect(),
+            states: meta.states.iter().map(|s| state(s)).collect(),
+            intermediates: meta
+                .intermediates
+                .iter()
+                .map(|i| RawIntermediate(i.to_string()))
+                .collect(),
+            references: meta
+                .references
+                .iter()
+                .map(|r| RawReference {
+                    state: state(r.state),
+                    mutable: r.mutable,
+                    resource: RawResource(r.resource.to_string()),
+                })
+                .collect(),
+            transitions: meta
+                .transitions
+                .iter()
+                .map(|t| RawTransition {
+                    from: state(t.from),
+                    intermediate: t.intermediate.map(|i| RawIntermediate(i.to_string())),
+                    to: state(t.to),
+                    after_ms: t.after_ms,
+                })
+                .collect(),
+            overlays: meta
+                .overlays
+                .iter()
+                .map(|o| RawOverlay {
+                    back: state(o.back),
+                    front: state(o.front),
+                })
+                .collect(),
+            layout: None,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::any::TypeId;
+use std::time::Duration;
+
+#[cfg(feature = "trace")]
+use super::Flow;
+#[cfg(feature = "trace")]
+use flower_parser::{
+    interpreter::Step,
+    trace::{Trace, TraceEntry},
+};
+
+#[cfg_attr(not(feature = "trace"), allow(dead_code))]
+pub(crate) enum Record {
+    Start(TypeId),
+    // `after` is the delay of the timer that fired, or of the timed transition taken by hand.
+    Transition {
+        from: TypeId,
+        via: TypeId,
+        to: TypeId,
+        after: Option<Duration>,
+    },
+    Push {
+        back: TypeId,
+        front: TypeId,
+    },
+    Pop(TypeId),
+    Event {
+        event: &'static str,
+        consumed_by: Option<TypeId>,
+    },
+}
+
+#[cfg(feature = "trace")]
+impl Record {
+    fn to_entry(&self, flow: &Flow) -> TraceEntry {
+        let name = |id| flow.name_of(id).to_string();
+        let step = match *self {
+            Record::Start(state) => return TraceEntry::Start { state: name(state) },
+            Record::Event { event, consumed_by } => {
+                return TraceEntry::Event {
+                    event: event.to_string(),
+                    consumed_by: consumed_by.map(name),
+                }
+            }
+            Record::Transition {
+                from,
+                via,
+                to,
+                after,
+            } => Step::Transition {
+                from: name(from),
+                intermediate: (via != to).then(|| name(via)),
+                to: name(to),
+                after_ms: after.map(|after| after.as_millis() as u64),
+            },
+            Record::Push { back, front } => Step::Push {
+                back: name(back),
+                front: name(front),
+            },
+            Record::Pop(front) => Step::Pop { front: name(front) },
+        };
+        TraceEntry::Step { step }
+    }
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn to_trace(records: &[Record], flow: &Flow) -> Trace {
+    Trace(records.iter().map(|r| r.to_entry(flow)).collect())
+}
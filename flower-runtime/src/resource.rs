@@ -0,0 +1,89 @@
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+
+use super::flow::Flow;
+
+#[derive(Default)]
+pub struct Resources(HashMap<TypeId, RefCell<Box<dyn Any>>>);
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<R: 'static>(mut self, resource: R) -> Self {
+        self.insert(resource);
+        self
+    }
+
+    pub fn insert<R: 'static>(&mut self, resource: R) {
+        self.0
+            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    fn cell<R: 'static>(&self) -> &RefCell<Box<dyn Any>> {
+        self.0
+            .get(&TypeId::of::<R>())
+            .unwrap_or_else(|| panic!("resource `{}` was not provided", std::any::type_name::<R>()))
+    }
+}
+
+// Gives a state access to the resources it declares in `reference:`.
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    flow: &'a Flow,
+    resources: &'a Resources,
+    state: TypeId,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(flow: &'a Flow, resources: &'a Resources, state: TypeId) -> Self {
+        Context {
+            flow,
+            resources,
+            state,
+        }
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        self.flow.name_of(self.state)
+    }
+
+    pub fn get<R: 'static>(&self) -> Ref<'a, R> {
+        self.check::<R>(false);
+        Ref::map(self.resources.cell::<R>().borrow(), |r| {
+            r.downcast_ref()
+                .expect("resource stored under its own type id")
+        })
+    }
+
+    pub fn get_mut<R: 'static>(&self) -> RefMut<'a, R> {
+        self.check::<R>(true);
+        RefMut::map(self.resources.cell::<R>().borrow_mut(), |r| {
+            r.downcast_mut()
+                .expect("resource stored under its own type id")
+        })
+    }
+
+    fn check<R: 'static>(&self, mutable: bool) {
+        let resource = TypeId::of::<R>();
+        let reference = self
+            .flow
+            .references_of(self.state)
+            .find(|r| r.resource == resource);
+        match reference {
+            Some(r) if r.mutable || !mutable => {}
+            Some(_) => panic!(
+                "state `{}` references `{}` immutably",
+                self.state_name(),
+                self.flow.name_of(resource)
+            ),
+            None => panic!(
+                "state `{}` does not reference `{}`",
+                self.state_name(),
+                self.flow.name_of(resource)
+            ),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::any::{Any, TypeId};
+use std::future::Future;
+use std::pin::Pin;
+
+use super::Context;
+
+pub type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+pub trait AsyncState: 'static {
+    fn on_enter<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+
+    fn update<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, Control> {
+        Box::pin(async { Control::Continue })
+    }
+
+    fn on_event<'a>(
+        &'a mut self,
+        _cx: Context<'a>,
+        _event: &'a dyn Any,
+    ) -> LocalBoxFuture<'a, Response> {
+        Box::pin(async { Response::Ignore })
+    }
+
+    fn on_exit<'a>(&'a mut self, _cx: Context<'a>) -> LocalBoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+pub enum Control {
+    Continue,
+    Transition(Box<dyn Any>),
+    Push(TypeId, Box<dyn AsyncState>),
+    Pop,
+}
+
+impl Control {
+    // `value` is either the intermediate of `S >- N -> T` or the next state itself for `S >--> T`.
+    pub fn transition<V: 'static>(value: V) -> Self {
+        Control::Transition(Box::new(value))
+    }
+
+    pub fn push<S: AsyncState>(state: S) -> Self {
+        Control::Push(TypeId::of::<S>(), Box::new(state))
+    }
+}
+
+// `Ignore` lets an event bubble down to the state beneath; `Consume` stops it there.
+pub enum Response {
+    Ignore,
+    Consume(Control),
+}
+
+impl Response {
+    pub fn consume() -> Self {
+        Response::Consume(Control::Continue)
+    }
+}